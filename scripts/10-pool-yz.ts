@@ -23,6 +23,8 @@ const WS_ENDPOINT = "wss://rpc.gorbchain.xyz/ws/";
 const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
 const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
 const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+// Global config PDA, passed last to InitPool, AddLiquidity, Swap and the multihop swaps
+const [CONFIG_PDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], AMM_PROGRAM_ID);
 
 const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
 const userKeypair = Keypair.fromSecretKey(
@@ -124,6 +126,12 @@ async function initPoolYZ() {
     // 6.1. Pool, vault, and LP mint accounts are created as PDAs by the program itself
     // 6.2. User LP ATA will be created by the program
 
+    // MINIMUM_LIQUIDITY LP tokens are minted to this PDA and locked forever
+    const [lockedLP] = await PublicKey.findProgramAddress(
+      [Buffer.from("locked_lp"), poolPDA.toBuffer()],
+      AMM_PROGRAM_ID
+    );
+
     // 6.3. Prepare accounts for InitPool (matching Rust program order)
     const accounts = [
       { pubkey: poolPDA, isSigner: false, isWritable: true },
@@ -139,7 +147,8 @@ async function initPoolYZ() {
       { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
       { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
-      { pubkey: ATA_PROGRAM_ID, isSigner: false, isWritable: false }, // ATA Program
+      { pubkey: lockedLP, isSigner: false, isWritable: true }, // locked_lp_info
+      { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
     ];
 
     // 6.7. Instruction data (Borsh: InitPool { amount_a, amount_b })
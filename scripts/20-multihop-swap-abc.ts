@@ -20,6 +20,8 @@ const WS_ENDPOINT = "wss://rpc.gorbchain.xyz/ws/";
 const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
 const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
 const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+// Global config PDA, passed last to InitPool, AddLiquidity, Swap and the multihop swaps
+const [CONFIG_PDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], AMM_PROGRAM_ID);
 const NATIVE_SOL_MINT = new PublicKey("So11111111111111111111111111111111111111112");
 
 const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
@@ -164,30 +166,25 @@ async function performMultihopSwap(swapInfo: MultihopSwapInfo): Promise<string>
       // 1. user_info (signer)
       // 2. token_program
       // 3. user_input_account (initial input token account)
-      // 4. For each hop (7 accounts): [pool, token_a, token_b, vault_a, vault_b, intermediate_token_account, next_token_account]
+      // 4. user_output_account (final output token account)
+      // 5. For each hop (3 accounts): [pool, vault_a, vault_b]
       accounts = [
         // User and program accounts
         { pubkey: userKeypair.publicKey, isSigner: true, isWritable: false },
         { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
         { pubkey: userTokenA, isSigner: false, isWritable: true }, // Initial input (Token A)
+        { pubkey: userTokenC, isSigner: false, isWritable: true }, // Final output (Token C)
         
-        // Hop 1: A → B (7 accounts: pool, token_a, token_b, vault_a, vault_b, intermediate, next)
+        // Hop 1: A → B (pool, vault_a, vault_b)
         { pubkey: new PublicKey("87pPUYxe8W1ExxksQJNFJyJTzGNy37RCjuu1v2ocQ9JJ"), isSigner: false, isWritable: true }, // A-B pool PDA
-        { pubkey: tokenA, isSigner: false, isWritable: false }, // Token A
-        { pubkey: tokenB, isSigner: false, isWritable: false }, // Token B
         { pubkey: new PublicKey("Dv4RzWgcxQi9EiDzTmY3HBfLdwaUBWcSyA7PGGqzgYqT"), isSigner: false, isWritable: true }, // A-B vault A
         { pubkey: new PublicKey("GHJduy4wxzcZNRRBVuXebcd2RTwqs7qGwtZVyZ4AezcV"), isSigner: false, isWritable: true }, // A-B vault B
-        { pubkey: userTokenB, isSigner: false, isWritable: true }, // Intermediate B
-        { pubkey: userTokenB, isSigner: false, isWritable: true }, // Next token account (same as intermediate for first hop)
         
-        // Hop 2: B → C (7 accounts: pool, token_a, token_b, vault_a, vault_b, intermediate, next)
+        // Hop 2: B → C (pool, vault_a, vault_b)
         { pubkey: new PublicKey("6qYwxHep4Svdz6Xf3xPYcqS62BYjRRCmVTUqQUVgZp45"), isSigner: false, isWritable: true }, // B-C pool PDA
-        { pubkey: tokenB, isSigner: false, isWritable: false }, // Token B
-        { pubkey: tokenC, isSigner: false, isWritable: false }, // Token C
         { pubkey: new PublicKey("2diUYyFqoDvtS9mfUso1Z4Mo5ikdLGrDNKK74jwd2xgw"), isSigner: false, isWritable: true }, // B-C vault A
         { pubkey: new PublicKey("EkJ7o1ZsDPceE76Xoj61qgTRpPrkBzTHXsipLSjDVrwr"), isSigner: false, isWritable: true }, // B-C vault B
-        { pubkey: userTokenB, isSigner: false, isWritable: true }, // Intermediate B
-        { pubkey: userTokenC, isSigner: false, isWritable: true }, // Final output (Token C)
+        { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
       ];
       
     } else {
@@ -199,24 +196,18 @@ async function performMultihopSwap(swapInfo: MultihopSwapInfo): Promise<string>
         { pubkey: userKeypair.publicKey, isSigner: true, isWritable: false },
         { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
         { pubkey: userTokenC, isSigner: false, isWritable: true }, // Initial input (Token C)
+        { pubkey: userTokenA, isSigner: false, isWritable: true }, // Final output (Token A)
         
-        // Hop 1: C → B (7 accounts: pool, token_a, token_b, vault_a, vault_b, intermediate, next)
+        // Hop 1: C → B (pool, vault_a, vault_b)
         { pubkey: new PublicKey("6qYwxHep4Svdz6Xf3xPYcqS62BYjRRCmVTUqQUVgZp45"), isSigner: false, isWritable: true }, // B-C pool PDA
-        { pubkey: tokenB, isSigner: false, isWritable: false }, // Token B
-        { pubkey: tokenC, isSigner: false, isWritable: false }, // Token C
         { pubkey: new PublicKey("2diUYyFqoDvtS9mfUso1Z4Mo5ikdLGrDNKK74jwd2xgw"), isSigner: false, isWritable: true }, // B-C vault A
         { pubkey: new PublicKey("EkJ7o1ZsDPceE76Xoj61qgTRpPrkBzTHXsipLSjDVrwr"), isSigner: false, isWritable: true }, // B-C vault B
-        { pubkey: userTokenB, isSigner: false, isWritable: true }, // Intermediate B
-        { pubkey: userTokenB, isSigner: false, isWritable: true }, // Next token account (same as intermediate for first hop)
         
-        // Hop 2: B → A (7 accounts: pool, token_a, token_b, vault_a, vault_b, intermediate, next)
+        // Hop 2: B → A (pool, vault_a, vault_b)
         { pubkey: new PublicKey("87pPUYxe8W1ExxksQJNFJyJTzGNy37RCjuu1v2ocQ9JJ"), isSigner: false, isWritable: true }, // A-B pool PDA
-        { pubkey: tokenA, isSigner: false, isWritable: false }, // Token A
-        { pubkey: tokenB, isSigner: false, isWritable: false }, // Token B
         { pubkey: new PublicKey("Dv4RzWgcxQi9EiDzTmY3HBfLdwaUBWcSyA7PGGqzgYqT"), isSigner: false, isWritable: true }, // A-B vault A
         { pubkey: new PublicKey("GHJduy4wxzcZNRRBVuXebcd2RTwqs7qGwtZVyZ4AezcV"), isSigner: false, isWritable: true }, // A-B vault B
-        { pubkey: userTokenB, isSigner: false, isWritable: true }, // Intermediate B
-        { pubkey: userTokenA, isSigner: false, isWritable: true }, // Final output (Token A)
+        { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
       ];
     }
 
@@ -21,6 +21,8 @@ const WS_ENDPOINT = "wss://rpc.gorbchain.xyz/ws/";
 const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
 const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
 const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+// Global config PDA, passed last to InitPool, AddLiquidity, Swap and the multihop swaps
+const [CONFIG_PDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], AMM_PROGRAM_ID);
 const NATIVE_SOL_MINT = new PublicKey("So11111111111111111111111111111111111111112");
 
 const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
@@ -179,6 +181,8 @@ async function addLiquidity(liquidityInfo: LiquidityInfo): Promise<string> {
       { pubkey: userLP, isSigner: false, isWritable: true }, // user_lp_info
       { pubkey: userKeypair.publicKey, isSigner: true, isWritable: true }, // user_info
       { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false }, // token_program
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false }, // system_program (native SOL pools)
+      { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
     ];
     
     // Add SystemProgram for native pools
@@ -20,6 +20,8 @@ const WS_ENDPOINT = "wss://rpc.gorbchain.xyz/ws/";
 const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
 const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
 const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+// Global config PDA, passed last to InitPool, AddLiquidity, Swap and the multihop swaps
+const [CONFIG_PDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], AMM_PROGRAM_ID);
 const NATIVE_SOL_MINT = new PublicKey("So11111111111111111111111111111111111111112");
 
 const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
@@ -199,6 +201,7 @@ async function performSwap(swapInfo: SwapInfo): Promise<string> {
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false }, // system_program
       { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false }, // rent
       { pubkey: ATA_PROGRAM_ID, isSigner: false, isWritable: false }, // ata_program
+      { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
     ];
     
     // Instruction data (Borsh: Swap { amount_in, direction })
@@ -17,6 +17,8 @@ const WS_ENDPOINT = "wss://rpc.gorbchain.xyz/ws/";
 const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
 const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
 const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+// Global config PDA, passed last to InitPool, AddLiquidity, Swap and the multihop swaps
+const [CONFIG_PDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], AMM_PROGRAM_ID);
 
 const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
 const userKeypair = Keypair.fromSecretKey(
@@ -108,6 +110,7 @@ async function addLiquidityXY() {
       { pubkey: USER_LP, isSigner: false, isWritable: true }, // user_lp_info
       { pubkey: userKeypair.publicKey, isSigner: true, isWritable: true }, // user_info
       { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false }, // token_program
+      { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
     ];
 
     // Instruction data (Borsh: AddLiquidity { amount_a, amount_b })
@@ -23,6 +23,8 @@ const WS_ENDPOINT = "wss://rpc.gorbchain.xyz/ws/";
 const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
 const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
 const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+// Global config PDA, passed last to InitPool, AddLiquidity, Swap and the multihop swaps
+const [CONFIG_PDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], AMM_PROGRAM_ID);
 
 const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
 const userKeypair = Keypair.fromSecretKey(
@@ -83,13 +85,15 @@ async function initPoolXNativeSOL() {
 
     // 3. Derive vault PDAs (matching Rust program logic)
     // Note: For native SOL pools, only the token vault is created as PDA
-    // SOL is stored directly in the pool account
+    // SOL is held in a separate system-owned lamport vault PDA
     const [vaultX, vaultXBump] = await PublicKey.findProgramAddress(
       [Buffer.from("native_sol_vault"), poolPDA.toBuffer(), TOKEN_X_MINT.toBuffer()],
       AMM_PROGRAM_ID
     );
-    // SOL vault is the pool account itself, not a separate PDA
-    const vaultSOL = poolPDA;
+    const [vaultSOL] = await PublicKey.findProgramAddress(
+      [Buffer.from("native_sol_lamport_vault"), poolPDA.toBuffer()],
+      AMM_PROGRAM_ID
+    );
     console.log(`Vault SOL: ${vaultSOL.toString()}`);
     console.log(`Vault X: ${vaultX.toString()}`);
 
@@ -121,12 +125,18 @@ async function initPoolXNativeSOL() {
     // 6.1. Pool, vault, and LP mint accounts are created as PDAs by the program itself
     // 6.2. User LP ATA will be created by the program
 
+    // MINIMUM_LIQUIDITY LP tokens are minted to this PDA and locked forever
+    const [lockedLP] = await PublicKey.findProgramAddress(
+      [Buffer.from("locked_lp"), poolPDA.toBuffer()],
+      AMM_PROGRAM_ID
+    );
+
     // 6.5. Prepare accounts for InitPool (matching contract order)
     const accounts = [
       { pubkey: poolPDA, isSigner: false, isWritable: true }, // pool_info
       { pubkey: NATIVE_SOL_MINT, isSigner: false, isWritable: false }, // token_a_info (SOL)
       { pubkey: TOKEN_X_MINT, isSigner: false, isWritable: false }, // token_b_info (Token X)
-      { pubkey: vaultSOL, isSigner: false, isWritable: true }, // vault_a (SOL vault - pool account itself)
+      { pubkey: vaultSOL, isSigner: false, isWritable: true }, // vault_a (SOL lamport vault PDA)
       { pubkey: vaultX, isSigner: false, isWritable: true }, // vault_b (Token X vault)
       { pubkey: lpMintPDA, isSigner: false, isWritable: true }, // lp_mint_info
       { pubkey: userKeypair.publicKey, isSigner: true, isWritable: true }, // user_info (needs to be writable for SOL transfer)
@@ -136,7 +146,8 @@ async function initPoolXNativeSOL() {
       { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false }, // token_program
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false }, // system_program
       { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false }, // rent
-      { pubkey: ATA_PROGRAM_ID, isSigner: false, isWritable: false }, // ata_program
+      { pubkey: lockedLP, isSigner: false, isWritable: true }, // locked_lp_info
+      { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
     ];
 
     // 6.6. Instruction data (Borsh: InitPool { amount_a, amount_b })
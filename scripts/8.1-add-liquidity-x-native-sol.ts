@@ -19,6 +19,8 @@ const WS_ENDPOINT = "wss://rpc.gorbchain.xyz/ws/";
 const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
 const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
 const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+// Global config PDA, passed last to InitPool, AddLiquidity, Swap and the multihop swaps
+const [CONFIG_PDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], AMM_PROGRAM_ID);
 
 const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
 const userKeypair = Keypair.fromSecretKey(
@@ -60,7 +62,10 @@ async function addLiquidityXNativeSOL() {
     const TOKEN_SOL_MINT = new PublicKey("So11111111111111111111111111111111111111112"); // Native SOL
     const TOKEN_X_MINT = new PublicKey(poolInfo.tokenB);
     const LP_MINT_PDA = new PublicKey(poolInfo.lpMint);
-    const VAULT_SOL = POOL_PDA; // SOL vault is the pool account itself for native SOL pools
+    const [VAULT_SOL] = await PublicKey.findProgramAddress(
+      [Buffer.from("native_sol_lamport_vault"), POOL_PDA.toBuffer()],
+      AMM_PROGRAM_ID
+    );
     const VAULT_X = new PublicKey(poolInfo.vaultB);
     const USER_TOKEN_SOL = userKeypair.publicKey; // User's main account for SOL
     const USER_TOKEN_X = new PublicKey(poolInfo.userTokenB);
@@ -102,7 +107,7 @@ async function addLiquidityXNativeSOL() {
       { pubkey: POOL_PDA, isSigner: false, isWritable: true }, // pool_info
       { pubkey: TOKEN_SOL_MINT, isSigner: false, isWritable: false }, // token_a_info (SOL)
       { pubkey: TOKEN_X_MINT, isSigner: false, isWritable: false }, // token_b_info (Token X)
-      { pubkey: VAULT_SOL, isSigner: false, isWritable: true }, // vault_a (SOL vault - pool account itself)
+      { pubkey: VAULT_SOL, isSigner: false, isWritable: true }, // vault_a (SOL lamport vault PDA)
       { pubkey: VAULT_X, isSigner: false, isWritable: true }, // vault_b (Token X vault)
       { pubkey: LP_MINT_PDA, isSigner: false, isWritable: true }, // lp_mint_info
       { pubkey: USER_TOKEN_SOL, isSigner: false, isWritable: true }, // user_token_a_info (SOL - user's main account)
@@ -111,6 +116,7 @@ async function addLiquidityXNativeSOL() {
       { pubkey: userKeypair.publicKey, isSigner: true, isWritable: true }, // user_info
       { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false }, // token_program
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false }, // system_program (required for SOL transfers)
+      { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
     ];
 
     // Instruction data (Borsh: AddLiquidity { amount_a, amount_b })
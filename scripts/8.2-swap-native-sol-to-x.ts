@@ -18,6 +18,8 @@ const WS_ENDPOINT = "wss://rpc.gorbchain.xyz/ws/";
 const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
 const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
 const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+// Global config PDA, passed last to InitPool, AddLiquidity, Swap and the multihop swaps
+const [CONFIG_PDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], AMM_PROGRAM_ID);
 
 const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
 const userKeypair = Keypair.fromSecretKey(
@@ -56,6 +58,10 @@ async function swapNativeSOLToX() {
     const poolInfo = JSON.parse(fs.readFileSync('pool-x-native-sol-info.json', 'utf-8'));
     
     const POOL_PDA = new PublicKey(poolInfo.poolPDA);
+    const [VAULT_SOL] = await PublicKey.findProgramAddress(
+      [Buffer.from("native_sol_lamport_vault"), POOL_PDA.toBuffer()],
+      AMM_PROGRAM_ID
+    );
     const TOKEN_X_MINT = new PublicKey(poolInfo.tokenB);
     const VAULT_X = new PublicKey(poolInfo.vaultB);
     const USER_TOKEN_X = new PublicKey(poolInfo.userTokenB);
@@ -87,13 +93,14 @@ async function swapNativeSOLToX() {
       { pubkey: POOL_PDA, isSigner: false, isWritable: true }, // pool_info
       { pubkey: new PublicKey("So11111111111111111111111111111111111111112"), isSigner: false, isWritable: false }, // token_a_info (SOL)
       { pubkey: TOKEN_X_MINT, isSigner: false, isWritable: false }, // token_b_info (Token X)
-      { pubkey: POOL_PDA, isSigner: false, isWritable: true }, // vault_a (SOL vault - pool account itself)
+      { pubkey: VAULT_SOL, isSigner: false, isWritable: true }, // vault_a (SOL lamport vault PDA)
       { pubkey: VAULT_X, isSigner: false, isWritable: true }, // vault_b (Token X vault)
       { pubkey: userKeypair.publicKey, isSigner: false, isWritable: true }, // user_in_info (SOL - user's main account)
       { pubkey: USER_TOKEN_X, isSigner: false, isWritable: true }, // user_out_info (Token X)
       { pubkey: userKeypair.publicKey, isSigner: true, isWritable: true }, // user_info
       { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false }, // token_program
       { pubkey: new PublicKey("11111111111111111111111111111111"), isSigner: false, isWritable: false }, // system_program
+      { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
     ];
 
     // Instruction data (Borsh: Swap { amount_in, direction_a_to_b })
@@ -5,6 +5,7 @@ import {
   Transaction,
   sendAndConfirmTransaction,
   LAMPORTS_PER_SOL,
+  SystemProgram,
 } from "@solana/web3.js";
 import {
   getAssociatedTokenAddressSync,
@@ -56,6 +57,10 @@ async function removeLiquidityXNativeSOL() {
     const poolInfo = JSON.parse(fs.readFileSync('pool-x-native-sol-info.json', 'utf-8'));
     
     const POOL_PDA = new PublicKey(poolInfo.poolPDA);
+    const [VAULT_SOL] = await PublicKey.findProgramAddress(
+      [Buffer.from("native_sol_lamport_vault"), POOL_PDA.toBuffer()],
+      AMM_PROGRAM_ID
+    );
     const TOKEN_X_MINT = new PublicKey(poolInfo.tokenB);
     const LP_MINT_PDA = new PublicKey(poolInfo.lpMint);
     const VAULT_X = new PublicKey(poolInfo.vaultB);
@@ -93,7 +98,7 @@ async function removeLiquidityXNativeSOL() {
       { pubkey: POOL_PDA, isSigner: false, isWritable: true }, // pool_info
       { pubkey: new PublicKey("So11111111111111111111111111111111111111112"), isSigner: false, isWritable: false }, // token_a_info (SOL)
       { pubkey: TOKEN_X_MINT, isSigner: false, isWritable: false }, // token_b_info (Token X)
-      { pubkey: POOL_PDA, isSigner: false, isWritable: true }, // vault_a (SOL vault - pool account itself)
+      { pubkey: VAULT_SOL, isSigner: false, isWritable: true }, // vault_a (SOL lamport vault PDA)
       { pubkey: VAULT_X, isSigner: false, isWritable: true }, // vault_b (Token X vault)
       { pubkey: LP_MINT_PDA, isSigner: false, isWritable: true }, // lp_mint_info
       { pubkey: USER_LP, isSigner: false, isWritable: true }, // user_lp_info
@@ -101,6 +106,7 @@ async function removeLiquidityXNativeSOL() {
       { pubkey: USER_TOKEN_X, isSigner: false, isWritable: true }, // user_token_b_info (Token X)
       { pubkey: userKeypair.publicKey, isSigner: true, isWritable: true }, // user_info
       { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false }, // token_program
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false }, // system_program (required for SOL transfers)
     ];
 
     // Instruction data (Borsh: RemoveLiquidity { lp_amount })
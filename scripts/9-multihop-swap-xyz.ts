@@ -19,6 +19,8 @@ const WS_ENDPOINT = "wss://rpc.gorbchain.xyz/ws/";
 const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
 const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
 const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+// Global config PDA, passed last to InitPool, AddLiquidity, Swap and the multihop swaps
+const [CONFIG_PDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], AMM_PROGRAM_ID);
 
 const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
 const userKeypair = Keypair.fromSecretKey(
@@ -114,30 +116,25 @@ async function multihopSwapXYZ() {
     // 1. user_info (signer)
     // 2. token_program
     // 3. user_input_account (initial input token account)
-    // 4. For each hop (7 accounts): [pool, token_a, token_b, vault_a, vault_b, intermediate_token_account, next_token_account]
+    // 4. user_output_account (final output token account)
+    // 5. For each hop (3 accounts): [pool, vault_a, vault_b]
     const accounts = [
       // User and program accounts
       { pubkey: userKeypair.publicKey, isSigner: true, isWritable: false },
       { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
       { pubkey: userTokenX, isSigner: false, isWritable: true }, // Initial input
+      { pubkey: userTokenZ, isSigner: false, isWritable: true }, // Final output
       
-      // Hop 1: X → Y (7 accounts: pool, token_a, token_b, vault_a, vault_b, intermediate, next)
+      // Hop 1: X → Y (pool, vault_a, vault_b)
       { pubkey: POOL_XY_PDA, isSigner: false, isWritable: true },
-      { pubkey: TOKEN_X_MINT, isSigner: false, isWritable: false },
-      { pubkey: TOKEN_Y_MINT, isSigner: false, isWritable: false },
       { pubkey: VAULT_X_XY, isSigner: false, isWritable: true },
       { pubkey: VAULT_Y_XY, isSigner: false, isWritable: true },
-      { pubkey: userTokenY, isSigner: false, isWritable: true }, // Intermediate Y
-      { pubkey: userTokenY, isSigner: false, isWritable: true }, // Next token account (same as intermediate for first hop)
       
-      // Hop 2: Y → Z (7 accounts: pool, token_a, token_b, vault_a, vault_b, intermediate, next)
+      // Hop 2: Y → Z (pool, vault_a, vault_b)
       { pubkey: POOL_YZ_PDA, isSigner: false, isWritable: true },
-      { pubkey: TOKEN_Y_MINT, isSigner: false, isWritable: false },
-      { pubkey: TOKEN_Z_MINT, isSigner: false, isWritable: false },
       { pubkey: VAULT_Y_YZ, isSigner: false, isWritable: true },
       { pubkey: VAULT_Z_YZ, isSigner: false, isWritable: true },
-      { pubkey: userTokenY, isSigner: false, isWritable: true }, // Intermediate Y
-      { pubkey: userTokenZ, isSigner: false, isWritable: true }, // Final output
+      { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
     ];
 
     // Instruction data (Borsh: MultihopSwap { amount_in, minimum_amount_out })
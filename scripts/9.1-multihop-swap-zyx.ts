@@ -19,6 +19,8 @@ const WS_ENDPOINT = "wss://rpc.gorbchain.xyz/ws/";
 const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
 const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
 const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+// Global config PDA, passed last to InitPool, AddLiquidity, Swap and the multihop swaps
+const [CONFIG_PDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], AMM_PROGRAM_ID);
 
 const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
 const userKeypair = Keypair.fromSecretKey(
@@ -115,24 +117,18 @@ async function multihopSwapZYX() {
       { pubkey: userKeypair.publicKey, isSigner: true, isWritable: false },
       { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
       { pubkey: userTokenZ, isSigner: false, isWritable: true }, // Initial input
+      { pubkey: userTokenX, isSigner: false, isWritable: true }, // Final output
       
-      // Hop 1: Z → Y (7 accounts: pool, token_a, token_b, vault_a, vault_b, intermediate, next)
+      // Hop 1: Z → Y (pool, vault_a, vault_b)
       { pubkey: POOL_YZ_PDA, isSigner: false, isWritable: true },
-      { pubkey: TOKEN_Y_MINT, isSigner: false, isWritable: false },
-      { pubkey: TOKEN_Z_MINT, isSigner: false, isWritable: false },
       { pubkey: VAULT_Y_YZ, isSigner: false, isWritable: true },
       { pubkey: VAULT_Z_YZ, isSigner: false, isWritable: true },
-      { pubkey: userTokenY, isSigner: false, isWritable: true }, // Intermediate Y
-      { pubkey: userTokenY, isSigner: false, isWritable: true }, // Next token account
       
-      // Hop 2: Y → X (7 accounts: pool, token_a, token_b, vault_a, vault_b, intermediate, next)
+      // Hop 2: Y → X (pool, vault_a, vault_b)
       { pubkey: POOL_XY_PDA, isSigner: false, isWritable: true },
-      { pubkey: TOKEN_X_MINT, isSigner: false, isWritable: false },
-      { pubkey: TOKEN_Y_MINT, isSigner: false, isWritable: false },
       { pubkey: VAULT_X_XY, isSigner: false, isWritable: true },
       { pubkey: VAULT_Y_XY, isSigner: false, isWritable: true },
-      { pubkey: userTokenY, isSigner: false, isWritable: true }, // Intermediate Y
-      { pubkey: userTokenX, isSigner: false, isWritable: true }, // Final output
+      { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
     ];
 
     // Instruction data (Borsh: MultihopSwap { amount_in, minimum_amount_out })
@@ -20,6 +20,8 @@ import {
   const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
   const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
   const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+  // Global config PDA, passed last to InitPool, AddLiquidity, Swap and the multihop swaps
+  const [CONFIG_PDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], AMM_PROGRAM_ID);
   const NATIVE_SOL_MINT = new PublicKey("So11111111111111111111111111111111111111112");
   
   const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
@@ -117,6 +119,12 @@ import {
     
     // Create transaction
     const transaction = new Transaction();
+    // MINIMUM_LIQUIDITY LP tokens are minted to this PDA and locked forever
+    const [lockedLP] = await PublicKey.findProgramAddress(
+      [Buffer.from("locked_lp"), poolPDA.toBuffer()],
+      AMM_PROGRAM_ID
+    );
+
     const accounts = [
       { pubkey: poolPDA, isSigner: false, isWritable: true },
       { pubkey: tokenA, isSigner: false, isWritable: false },
@@ -131,7 +139,8 @@ import {
       { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
       { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
-      { pubkey: ATA_PROGRAM_ID, isSigner: false, isWritable: false },
+      { pubkey: lockedLP, isSigner: false, isWritable: true }, // locked_lp_info
+      { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
     ];
     
     const data = Buffer.alloc(1 + 8 + 8);
@@ -192,6 +201,11 @@ import {
       AMM_PROGRAM_ID
     );
     
+    const [vaultSOL] = await PublicKey.findProgramAddress(
+      [Buffer.from("native_sol_lamport_vault"), poolPDA.toBuffer()],
+      AMM_PROGRAM_ID
+    );
+    
     const userToken = getAssociatedTokenAddressSync(token, userKeypair.publicKey, false, SPL_TOKEN_PROGRAM_ID, ATA_PROGRAM_ID);
     const userLP = getAssociatedTokenAddressSync(lpMint, userKeypair.publicKey, false, SPL_TOKEN_PROGRAM_ID, ATA_PROGRAM_ID);
     
@@ -205,11 +219,17 @@ import {
     
     // Create transaction
     const transaction = new Transaction();
+    // MINIMUM_LIQUIDITY LP tokens are minted to this PDA and locked forever
+    const [lockedLP] = await PublicKey.findProgramAddress(
+      [Buffer.from("locked_lp"), poolPDA.toBuffer()],
+      AMM_PROGRAM_ID
+    );
+
     const accounts = [
       { pubkey: poolPDA, isSigner: false, isWritable: true },
       { pubkey: NATIVE_SOL_MINT, isSigner: false, isWritable: false },
       { pubkey: token, isSigner: false, isWritable: false },
-      { pubkey: poolPDA, isSigner: false, isWritable: true }, // SOL vault is pool account
+      { pubkey: vaultSOL, isSigner: false, isWritable: true }, // SOL lamport vault PDA
       { pubkey: vaultToken, isSigner: false, isWritable: true },
       { pubkey: lpMint, isSigner: false, isWritable: true },
       { pubkey: userKeypair.publicKey, isSigner: true, isWritable: true },
@@ -219,7 +239,8 @@ import {
       { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
       { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
-      { pubkey: ATA_PROGRAM_ID, isSigner: false, isWritable: false },
+      { pubkey: lockedLP, isSigner: false, isWritable: true }, // locked_lp_info
+      { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
     ];
     
     const data = Buffer.alloc(1 + 8 + 8);
@@ -252,7 +273,7 @@ import {
       tokenB: token,
       poolPDA,
       lpMint,
-      vaultA: poolPDA, // SOL vault is pool account
+      vaultA: vaultSOL, // SOL lamport vault PDA
       vaultB: vaultToken,
       userTokenA: userKeypair.publicKey, // SOL account
       userTokenB: userToken,
@@ -555,6 +576,7 @@ import {
         { pubkey: poolConfig.userLP, isSigner: false, isWritable: true },
         { pubkey: userKeypair.publicKey, isSigner: true, isWritable: true },
         { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+        { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
       ];
     } else {
       accounts = [
@@ -570,6 +592,7 @@ import {
         { pubkey: userKeypair.publicKey, isSigner: true, isWritable: true },
         { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
       ];
     }
     
@@ -630,6 +653,7 @@ import {
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
         { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
         { pubkey: ATA_PROGRAM_ID, isSigner: false, isWritable: false },
+        { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
       ];
     } else {
       accounts = [
@@ -643,6 +667,7 @@ import {
         { pubkey: userKeypair.publicKey, isSigner: true, isWritable: true },
         { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
       ];
     }
     
@@ -726,6 +751,7 @@ import {
         { pubkey: poolConfig.userTokenB, isSigner: false, isWritable: true },
         { pubkey: userKeypair.publicKey, isSigner: true, isWritable: true },
         { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
       ];
     }
     
@@ -5,12 +5,15 @@ import {
   Transaction,
   sendAndConfirmTransaction,
 } from "@solana/web3.js";
+import { getAssociatedTokenAddressSync } from "@solana/spl-token";
 import * as fs from "fs";
 
 // Configuration
 const RPC_ENDPOINT = "https://rpc.gorbchain.xyz";
 const WS_ENDPOINT = "wss://rpc.gorbchain.xyz/ws/";
 const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
+const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
+const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
 
 const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
 const userKeypair = Keypair.fromSecretKey(
@@ -24,7 +27,8 @@ const connection = new Connection(RPC_ENDPOINT, {
 
 /**
  * TypeScript Script: Collect Fees from Pool
- * This script collects (views and resets) accumulated trading fees from a pool
+ * This script transfers the accrued protocol fees from the pool vaults to the
+ * fee treasury's token accounts and resets the pool's fee counters
  */
 async function collectFees() {
   try {
@@ -33,9 +37,20 @@ async function collectFees() {
     // Load pool info (using X-Y pool as example)
     const poolXYInfo = JSON.parse(fs.readFileSync('pool-xy-info.json', 'utf-8'));
     const POOL_XY_PDA = new PublicKey(poolXYInfo.poolPDA);
+    const VAULT_X = new PublicKey(poolXYInfo.vaultX);
+    const VAULT_Y = new PublicKey(poolXYInfo.vaultY);
+    const TOKEN_X_MINT = new PublicKey(poolXYInfo.tokenX);
+    const TOKEN_Y_MINT = new PublicKey(poolXYInfo.tokenY);
+    
+    // Fees are paid into the treasury's token accounts, so the treasury
+    // (set with fee-2-set-treasury.ts) must own an ATA for both pool tokens
+    const treasuryTokenX = getAssociatedTokenAddressSync(TOKEN_X_MINT, userKeypair.publicKey, false, SPL_TOKEN_PROGRAM_ID, ATA_PROGRAM_ID);
+    const treasuryTokenY = getAssociatedTokenAddressSync(TOKEN_Y_MINT, userKeypair.publicKey, false, SPL_TOKEN_PROGRAM_ID, ATA_PROGRAM_ID);
     
     console.log(`Pool PDA: ${POOL_XY_PDA.toString()}`);
     console.log(`Treasury: ${userKeypair.publicKey.toString()}`); // Using user as treasury for demo
+    console.log(`Treasury Token X: ${treasuryTokenX.toString()}`);
+    console.log(`Treasury Token Y: ${treasuryTokenY.toString()}`);
 
     // Create transaction
     const transaction = new Transaction();
@@ -43,8 +58,13 @@ async function collectFees() {
     // Prepare accounts for CollectFees
     const accounts = [
       { pubkey: POOL_XY_PDA, isSigner: false, isWritable: true },           // pool_info
-      { pubkey: userKeypair.publicKey, isSigner: false, isWritable: false }, // treasury_info
+      { pubkey: userKeypair.publicKey, isSigner: false, isWritable: true },  // treasury_info
       { pubkey: userKeypair.publicKey, isSigner: true, isWritable: false },  // authority_info
+      { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },  // token_program_info
+      { pubkey: VAULT_X, isSigner: false, isWritable: true },                // vault_a_info
+      { pubkey: VAULT_Y, isSigner: false, isWritable: true },                // vault_b_info
+      { pubkey: treasuryTokenX, isSigner: false, isWritable: true },         // treasury_a_info
+      { pubkey: treasuryTokenY, isSigner: false, isWritable: true },         // treasury_b_info
     ];
 
     // Instruction data (Borsh: CollectFees { pool })
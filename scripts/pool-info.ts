@@ -35,8 +35,22 @@ import {
     feeCollectedSol?: number;
     feeCollectedToken?: number;
     feeTreasury?: string;
+    // Fields of account-type-prefixed pools
+    version?: number;
+    authority?: string;
+    protocolFeeDivisor?: number;
+    paused?: boolean;
+    lastUpdateTimestamp?: number;
   }
   
+  // First byte of every program account (AccountType)
+  const ACCOUNT_TYPE_POOL = 1;
+  const ACCOUNT_TYPE_NATIVE_SOL_POOL = 2;
+  
+  // Sizes of the current account-type-prefixed pool layouts
+  const POOL_LEN = 271;
+  const NATIVE_SOL_POOL_LEN = 303;
+  
   function formatTokenAmount(amount: number, decimals: number = 6): string {
     const formatted = amount / Math.pow(10, decimals);
     return formatted.toLocaleString('en-US', {
@@ -50,7 +64,64 @@ import {
       const dataLength = data.length;
       const rawData = data.toString('hex');
       
-      if (dataLength === 89) {
+      if (dataLength === POOL_LEN && data[0] === ACCOUNT_TYPE_POOL) {
+        // Regular Pool: account_type(1) + version(1) + token_a(32) + token_b(32) + bump(1) + reserve_a(8) + reserve_b(8) + total_lp_supply(8)
+        // + fee_collected_a(8) + fee_collected_b(8) + fee_treasury(32) + authority(32) + pending_authority(32) + fee_bps(2)
+        // + protocol_fee_divisor(1) + status(1) + price_a_cumulative(16) + price_b_cumulative(16) + last_update_timestamp(8) + reserved(24)
+        const feeBps = data.readUInt16LE(203);
+        
+        return {
+          poolAddress,
+          poolType: 'Regular',
+          dataLength,
+          rawData,
+          version: data[1],
+          tokenA: new PublicKey(data.slice(2, 34)).toString(),
+          tokenB: new PublicKey(data.slice(34, 66)).toString(),
+          bump: data[66],
+          reserveA: Number(data.readBigUInt64LE(67)),
+          reserveB: Number(data.readBigUInt64LE(75)),
+          totalLPSupply: Number(data.readBigUInt64LE(83)),
+          feeCollectedA: Number(data.readBigUInt64LE(91)),
+          feeCollectedB: Number(data.readBigUInt64LE(99)),
+          feeTreasury: new PublicKey(data.slice(107, 139)).toString(),
+          authority: new PublicKey(data.slice(139, 171)).toString(),
+          feeBps,
+          feePercentage: feeBps / 100,
+          protocolFeeDivisor: data[205],
+          paused: data[206] === 1,
+          lastUpdateTimestamp: Number(data.readBigInt64LE(239))
+        };
+        
+      } else if (dataLength === NATIVE_SOL_POOL_LEN && data[0] === ACCOUNT_TYPE_NATIVE_SOL_POOL) {
+        // Native SOL Pool: account_type(1) + version(1) + token_a(32) + token_b(32) + bump(1) + reserve_a(8) + reserve_b(8) + total_lp_supply(8)
+        // + fee_collected_sol(8) + fee_collected_token(8) + fee_treasury(32) + token_mint(32) + authority(32) + pending_authority(32) + fee_bps(2)
+        // + protocol_fee_divisor(1) + status(1) + price_a_cumulative(16) + price_b_cumulative(16) + last_update_timestamp(8) + reserved(24)
+        const feeBps = data.readUInt16LE(235);
+        
+        return {
+          poolAddress,
+          poolType: 'Native SOL',
+          dataLength,
+          rawData,
+          version: data[1],
+          tokenMint: new PublicKey(data.slice(139, 171)).toString(),
+          bump: data[66],
+          solReserve: Number(data.readBigUInt64LE(67)),
+          tokenReserve: Number(data.readBigUInt64LE(75)),
+          totalLPSupply: Number(data.readBigUInt64LE(83)),
+          feeCollectedSol: Number(data.readBigUInt64LE(91)),
+          feeCollectedToken: Number(data.readBigUInt64LE(99)),
+          feeTreasury: new PublicKey(data.slice(107, 139)).toString(),
+          authority: new PublicKey(data.slice(171, 203)).toString(),
+          feeBps,
+          feePercentage: feeBps / 100,
+          protocolFeeDivisor: data[237],
+          paused: data[238] === 1,
+          lastUpdateTimestamp: Number(data.readBigInt64LE(271))
+        };
+        
+      } else if (dataLength === 89) {
         // Old Regular Pool structure: token_a(32) + token_b(32) + bump(1) + reserve_a(8) + reserve_b(8) + total_lp_supply(8)
         const tokenA = new PublicKey(data.slice(0, 32));
         const tokenB = new PublicKey(data.slice(32, 64));
@@ -211,6 +282,13 @@ import {
       console.log(`   Fee: ${pool.feeBps} bps (${pool.feePercentage.toFixed(2)}%)`);
       console.log(`   Total LP Supply: ${pool.totalLPSupply.toLocaleString()}`);
       console.log(`   Bump: ${pool.bump}`);
+      if (pool.version !== undefined) {
+        console.log(`   Version: ${pool.version}`);
+        console.log(`   Status: ${pool.paused ? 'Paused' : 'Active'}`);
+        console.log(`   Authority: ${pool.authority}`);
+        console.log(`   Protocol Fee Divisor: ${pool.protocolFeeDivisor || 'off'}`);
+        console.log(`   Last Price Update: ${pool.lastUpdateTimestamp}`);
+      }
       
       // Show fee information for new pools
       if (pool.feeBps > 0) {
@@ -20,6 +20,8 @@ const WS_ENDPOINT = "wss://rpc.gorbchain.xyz/ws/";
 const AMM_PROGRAM_ID = new PublicKey("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
 const SPL_TOKEN_PROGRAM_ID = new PublicKey("G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6");
 const ATA_PROGRAM_ID = new PublicKey("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm");
+// Global config PDA, passed last to InitPool, AddLiquidity, Swap and the multihop swaps
+const [CONFIG_PDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], AMM_PROGRAM_ID);
 const NATIVE_SOL_MINT = new PublicKey("So11111111111111111111111111111111111111112");
 
 const USER_KEYPAIR_PATH = "/home/saurabh/.config/solana/id.json";
@@ -180,7 +182,10 @@ async function createPool(
         AMM_PROGRAM_ID
       );
       
-      vaultA = poolPDA; // SOL vault is the pool account itself
+      [vaultA] = await PublicKey.findProgramAddress(
+        [Buffer.from("native_sol_lamport_vault"), poolPDA.toBuffer()],
+        AMM_PROGRAM_ID
+      );
       
       [vaultB] = await PublicKey.findProgramAddress(
         [Buffer.from("native_sol_vault"), poolPDA.toBuffer(), tokenB.toBuffer()],
@@ -217,6 +222,12 @@ async function createPool(
     // Create transaction
     const transaction = new Transaction();
     
+    // MINIMUM_LIQUIDITY LP tokens are minted to this PDA and locked forever
+    const [lockedLP] = await PublicKey.findProgramAddress(
+      [Buffer.from("locked_lp"), poolPDA.toBuffer()],
+      AMM_PROGRAM_ID
+    );
+
     // Prepare accounts based on pool type
     const accounts = [
       { pubkey: poolPDA, isSigner: false, isWritable: true },
@@ -232,7 +243,8 @@ async function createPool(
       { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
       { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
-      { pubkey: ATA_PROGRAM_ID, isSigner: false, isWritable: false },
+      { pubkey: lockedLP, isSigner: false, isWritable: true }, // locked_lp_info
+      { pubkey: CONFIG_PDA, isSigner: false, isWritable: false }, // Global config, always last
     ];
     
     // Instruction data (Borsh: InitPool { amount_a, amount_b })
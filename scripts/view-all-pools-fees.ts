@@ -43,13 +43,22 @@ function formatTokenAmount(amount: bigint, decimals: number = 9): string {
   return (Number(amount) / Math.pow(10, decimals)).toFixed(6);
 }
 
+// Pools start with account_type (1) + version (1). Legacy pools that have not
+// been through MigratePool have no prefix and keep their old size.
+const POOL_HEADER_LEN = 2;
+const LEGACY_POOL_LEN = 137;
+const LEGACY_NATIVE_SOL_POOL_LEN = 169;
+
+function poolFieldsOffset(data: Buffer, legacyLen: number): number {
+  return data.length === legacyLen ? 0 : POOL_HEADER_LEN;
+}
+
 // Helper function to parse pool data
 function parsePoolData(data: Buffer): Pool | null {
   try {
-    // Pool struct: token_a(32) + token_b(32) + bump(1) + reserve_a(8) + reserve_b(8) + total_lp_supply(8) + fee_collected_a(8) + fee_collected_b(8) + fee_treasury(32) = 137 bytes
-    if (data.length < 137) return null;
-    
-    let offset = 0; // No discriminator for Borsh
+    // Pool struct after the header: token_a(32) + token_b(32) + bump(1) + reserve_a(8) + reserve_b(8) + total_lp_supply(8) + fee_collected_a(8) + fee_collected_b(8) + fee_treasury(32) + ...
+    let offset = poolFieldsOffset(data, LEGACY_POOL_LEN);
+    if (data.length < offset + 137) return null;
     
     const token_a = new PublicKey(data.slice(offset, offset + 32));
     offset += 32;
@@ -88,10 +97,9 @@ function parsePoolData(data: Buffer): Pool | null {
 // Helper function to parse native SOL pool data
 function parseNativeSOLPoolData(data: Buffer): NativeSOLPool | null {
   try {
-    // NativeSOLPool struct: token_a(32) + token_b(32) + bump(1) + reserve_a(8) + reserve_b(8) + total_lp_supply(8) + fee_collected_sol(8) + fee_collected_token(8) + fee_treasury(32) = 137 bytes
-    if (data.length < 137) return null;
-    
-    let offset = 0; // No discriminator for Borsh
+    // NativeSOLPool struct after the header: token_a(32) + token_b(32) + bump(1) + reserve_a(8) + reserve_b(8) + total_lp_supply(8) + fee_collected_sol(8) + fee_collected_token(8) + fee_treasury(32) + ...
+    let offset = poolFieldsOffset(data, LEGACY_NATIVE_SOL_POOL_LEN);
+    if (data.length < offset + 137) return null;
     
     const token_a = new PublicKey(data.slice(offset, offset + 32));
     offset += 32;
@@ -1,5 +1,7 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable,
+    clock::Clock,
     entrypoint,
     entrypoint::ProgramResult,
     program::{invoke, invoke_signed},
@@ -13,17 +15,99 @@ use solana_program::{
 
 // Native SOL mint address (wrapped SOL)
 const NATIVE_SOL_MINT: Pubkey = solana_program::pubkey!("So11111111111111111111111111111111111111112");
-// use spl_token::state::Account as TokenAccount; // Unused import
+use spl_token::state::Account as TokenAccount;
 use std::str::FromStr;
 use borsh::{BorshDeserialize, BorshSerialize};
 use shank::{ShankInstruction, ShankAccount};
 
+mod math;
+
 // Program ID
 solana_program::declare_id!("EtGrXaRpEdozMtfd8tbkbrbDN8LqZNba3xWTdT3HtQWq");
 
 // GorbChain SPL Token Program ID
 const GORBCHAIN_SPL_TOKEN_PROGRAM: &str = "G22oYgZ6LnVcy7v8eSNi2xpNk1NcZiPD8CVKSTut7oZ6";
 
+// Program errors, surfaced to clients as ProgramError::Custom(code).
+// Codes are part of the public API: never renumber, only append.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwapError {
+    /// 0 - Output amount is below the caller's minimum
+    SlippageExceeded = 0,
+    /// 1 - Pool account does not match the expected PDA or instruction argument
+    InvalidPoolAddress = 1,
+    /// 2 - Vault account does not match the pool's vault PDA
+    InvalidVault = 2,
+    /// 3 - LP mint account does not match the pool's LP mint PDA
+    InvalidLpMint = 3,
+    /// 4 - Pool account is not of the type implied by the instruction accounts
+    PoolTypeMismatch = 4,
+    /// 5 - Arithmetic overflow, underflow or division by zero
+    MathOverflow = 5,
+    /// 6 - Pool has no reserves or no LP supply
+    ZeroLiquidity = 6,
+    /// 7 - Input amount is zero or rounds down to zero
+    ZeroAmount = 7,
+    /// 8 - Signer is not allowed to manage the pool's fees
+    UnauthorizedTreasury = 8,
+    /// 9 - Treasury account does not match the pool's fee treasury
+    InvalidTreasury = 9,
+    /// 10 - Requested fee withdrawal exceeds the accrued fees
+    InsufficientFees = 10,
+    /// 11 - Token mint is not part of the pool or does not follow the swap path
+    InvalidTokenMint = 11,
+    /// 12 - Swap path is too short or does not match the supplied accounts
+    InvalidSwapPath = 12,
+    /// 13 - Price impact of the swap is above the caller's maximum
+    PriceImpactExceeded = 13,
+    /// 14 - Requested output is not less than the pool's reserve
+    InsufficientLiquidity = 14,
+    /// 15 - Transaction executed after its valid_until deadline
+    DeadlineExceeded = 15,
+    /// 16 - Account is not owned by the expected program
+    InvalidAccountOwner = 16,
+    /// 17 - Account must be passed as writable
+    AccountNotWritable = 17,
+    /// 18 - Program account does not match the expected program id
+    InvalidProgramId = 18,
+    /// 19 - Signer is not the pool authority (or the pending authority when accepting)
+    InvalidAuthority = 19,
+    /// 20 - Vault holds less than the pool's reserve plus unclaimed protocol fees
+    VaultBalanceMismatch = 20,
+    /// 21 - Fee is not one of the allowed fee tiers
+    InvalidFeeTier = 21,
+    /// 22 - Protocol fee divisor is out of range
+    InvalidProtocolFee = 22,
+    /// 23 - Program is paused by the config admin
+    ProgramPaused = 23,
+    /// 24 - Config account does not match the config PDA
+    InvalidConfig = 24,
+    /// 25 - Pool is paused by the config admin
+    PoolPaused = 25,
+    /// 26 - Initial deposit mints no more than MINIMUM_LIQUIDITY LP tokens
+    InitialLiquidityTooLow = 26,
+    /// 27 - Locked LP account does not match the pool's locked LP PDA
+    InvalidLockedLpAccount = 27,
+    /// 28 - Vault received a different amount than was transferred (fee-on-transfer token)
+    TransferAmountMismatch = 28,
+    /// 29 - wSOL account holds less than the SOL amount to unwrap
+    InsufficientWrappedSol = 29,
+    /// 30 - Account discriminator does not match the expected account type
+    InvalidAccountType = 30,
+    /// 31 - Pool account uses an older layout and must be migrated with MigratePool
+    PoolNeedsMigration = 31,
+    /// 32 - Split route weights are zero or do not add up to 10000 bps
+    InvalidSplitWeights = 32,
+    /// 33 - Observation account does not match the pool's observation PDA
+    InvalidObservationAccount = 33,
+}
+
+impl From<SwapError> for ProgramError {
+    fn from(e: SwapError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
 // Manual instruction creation for GorbChain SPL Token program
 fn create_transfer_instruction(
     source: &Pubkey,
@@ -99,11 +183,7 @@ fn create_initialize_account_instruction(
     mint: &Pubkey,
     authority: &Pubkey,
 ) -> solana_program::instruction::Instruction {
-    let data = {
-        let mut buf = Vec::new();
-        buf.push(1); // InitializeAccount instruction discriminator
-        buf
-    };
+    let data = vec![1]; // InitializeAccount instruction discriminator
     solana_program::instruction::Instruction {
         program_id: Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
         accounts: vec![
@@ -116,6 +196,34 @@ fn create_initialize_account_instruction(
     }
 }
 
+fn create_close_account_instruction(
+    account: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+) -> solana_program::instruction::Instruction {
+    let data = vec![9]; // CloseAccount instruction discriminator
+    solana_program::instruction::Instruction {
+        program_id: Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
+        accounts: vec![
+            solana_program::instruction::AccountMeta::new(*account, false),
+            solana_program::instruction::AccountMeta::new(*destination, false),
+            solana_program::instruction::AccountMeta::new_readonly(*owner, true),
+        ],
+        data,
+    }
+}
+
+fn create_sync_native_instruction(account: &Pubkey) -> solana_program::instruction::Instruction {
+    let data = vec![17]; // SyncNative instruction discriminator
+    solana_program::instruction::Instruction {
+        program_id: Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
+        accounts: vec![
+            solana_program::instruction::AccountMeta::new(*account, false),
+        ],
+        data,
+    }
+}
+
 // InitializeMint instruction for GorbChain SPL Token program
 fn create_initialize_mint_instruction(
     mint: &Pubkey,
@@ -153,11 +261,7 @@ fn create_associated_token_account_instruction(
     owner: &Pubkey,
     mint: &Pubkey,
 ) -> solana_program::instruction::Instruction {
-    let data = {
-        let mut buf = Vec::new();
-        buf.push(0); // CreateAssociatedTokenAccount instruction discriminator
-        buf
-    };
+    let data = vec![0]; // CreateAssociatedTokenAccount instruction discriminator
     solana_program::instruction::Instruction {
         program_id: Pubkey::from_str("GoATGVNeSXerFerPqTJ8hcED1msPWHHLxao2vwBYqowm").unwrap(), // ATA Program ID
         accounts: vec![
@@ -181,486 +285,2146 @@ fn get_vault_address(pool: &Pubkey, token_mint: &Pubkey, program_id: &Pubkey) ->
     )
 }
 
-// Entry point
-entrypoint!(process_instruction);
+// Account validation
+//
+// Every handler builds a typed account context from the raw `accounts` slice
+// before doing anything else. Loading a context enforces the signer, writable,
+// owner and program-id constraints of the instruction, so spoofed accounts are
+// rejected before any state is read or any CPI is made.
 
-// Instructions
-#[derive(BorshSerialize, BorshDeserialize, Debug, ShankInstruction)]
-pub enum TestProjectInstruction {
-    InitPool { amount_a: u64, amount_b: u64 },
-    AddLiquidity { amount_a: u64, amount_b: u64 },
-    RemoveLiquidity { lp_amount: u64 },
-    Swap { amount_in: u64, direction_a_to_b: bool },
-    MultihopSwap { amount_in: u64, minimum_amount_out: u64 },
-    MultihopSwapWithPath { amount_in: u64, minimum_amount_out: u64, token_path: Vec<Pubkey> },
-    CollectFees { pool: Pubkey },
-    WithdrawFees { pool: Pubkey, amount_a: u64, amount_b: u64 },
-    SetFeeTreasury { pool: Pubkey, treasury: Pubkey },
+fn gorbchain_token_program_id() -> Pubkey {
+    Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap()
 }
 
-// Pool state
-#[derive(BorshSerialize, BorshDeserialize, Debug, ShankAccount)]
-pub struct Pool {
-    pub token_a: Pubkey,
-    pub token_b: Pubkey,
-    pub bump: u8,
-    pub reserve_a: u64,
-    pub reserve_b: u64,
-    pub total_lp_supply: u64,
-    pub fee_collected_a: u64,
-    pub fee_collected_b: u64,
-    pub fee_treasury: Pubkey,
+fn check_signer(info: &AccountInfo) -> ProgramResult {
+    if !info.is_signer {
+        solana_program::log::sol_log(&format!("Missing signature: {}", info.key));
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
 }
 
-impl Sealed for Pool {}
-
-impl IsInitialized for Pool {
-    fn is_initialized(&self) -> bool {
-        self.token_a != Pubkey::default()
+fn check_writable(info: &AccountInfo) -> ProgramResult {
+    if !info.is_writable {
+        solana_program::log::sol_log(&format!("Account not writable: {}", info.key));
+        return Err(SwapError::AccountNotWritable.into());
     }
+    Ok(())
 }
 
-impl Pack for Pool {
-    const LEN: usize = 32 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 32; // 137 bytes
-    
-    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let pool = Pool::try_from_slice(src)
-            .map_err(|_| ProgramError::InvalidAccountData)?;
-        Ok(pool)
+fn check_owner(info: &AccountInfo, owner: &Pubkey) -> ProgramResult {
+    if info.owner != owner {
+        solana_program::log::sol_log(&format!("Invalid owner for {}: {}", info.key, info.owner));
+        return Err(SwapError::InvalidAccountOwner.into());
     }
-    
-    fn pack_into_slice(&self, dst: &mut [u8]) {
-        let data = self.try_to_vec().unwrap();
-        dst[..data.len()].copy_from_slice(&data);
+    Ok(())
+}
+
+fn check_program_id(info: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
+    if info.key != program_id {
+        solana_program::log::sol_log(&format!("Invalid program: expected {}, got {}", program_id, info.key));
+        return Err(SwapError::InvalidProgramId.into());
     }
+    Ok(())
 }
 
-// Native SOL Pool state
-#[derive(BorshSerialize, BorshDeserialize, Debug, ShankAccount)]
-pub struct NativeSOLPool {
-    pub token_a: Pubkey,     // Native SOL address (So11111111111111111111111111111111111111112)
-    pub token_b: Pubkey,     // The SPL token mint
-    pub bump: u8,
-    pub reserve_a: u64,      // Native SOL reserve
-    pub reserve_b: u64,      // SPL token reserve
-    pub total_lp_supply: u64,
-    pub fee_collected_sol: u64,
-    pub fee_collected_token: u64,
-    pub fee_treasury: Pubkey,
-    pub token_mint: Pubkey,  // The SPL token mint (same as token_b for convenience)
+// Initialized pool state account owned by this program
+fn check_pool_account(info: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
+    check_writable(info)?;
+    check_owner(info, program_id)
 }
 
-impl Sealed for NativeSOLPool {}
+// Vault, LP mint or user token account held by the GorbChain token program
+fn check_token_account(info: &AccountInfo) -> ProgramResult {
+    check_writable(info)?;
+    check_owner(info, &gorbchain_token_program_id())
+}
 
-impl IsInitialized for NativeSOLPool {
-    fn is_initialized(&self) -> bool {
-        self.token_a != Pubkey::default()
+// Token vault, or the system-owned SOL vault of a native pool
+fn check_vault_owner(info: &AccountInfo) -> ProgramResult {
+    if *info.owner == solana_program::system_program::id() {
+        return Ok(());
     }
+    check_owner(info, &gorbchain_token_program_id())
 }
 
-impl Pack for NativeSOLPool {
-    const LEN: usize = 32 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 32 + 32; // 169 bytes
-    
-    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let pool = NativeSOLPool::try_from_slice(src)
-            .map_err(|_| ProgramError::InvalidAccountData)?;
-        Ok(pool)
-    }
-    
-    fn pack_into_slice(&self, dst: &mut [u8]) {
-        let data = self.try_to_vec().unwrap();
-        dst[..data.len()].copy_from_slice(&data);
+// Wallet paying or receiving lamports and authorizing token transfers
+fn check_user_wallet(info: &AccountInfo) -> ProgramResult {
+    check_signer(info)?;
+    check_writable(info)
+}
+
+// Token accounts may carry extensions after the base layout, only the base is read
+fn unpack_token_account(info: &AccountInfo) -> Result<TokenAccount, ProgramError> {
+    let data = info.data.borrow();
+    let token_account = data
+        .get(..TokenAccount::LEN)
+        .ok_or(ProgramError::InvalidAccountData)
+        .and_then(TokenAccount::unpack_from_slice)?;
+    if !token_account.is_initialized() {
+        return Err(ProgramError::UninitializedAccount);
     }
+    Ok(token_account)
 }
 
-// Program instruction processor
-pub fn process_instruction(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    instruction_data: &[u8],
-) -> ProgramResult {
-    let instruction = TestProjectInstruction::try_from_slice(instruction_data)
-        .map_err(|_| ProgramError::InvalidInstructionData)?;
-        
-    match instruction {
-        TestProjectInstruction::InitPool { amount_a, amount_b } => {
-            process_init_pool(program_id, accounts, amount_a, amount_b)
-        }
-        TestProjectInstruction::AddLiquidity { amount_a, amount_b } => {
-            process_add_liquidity(program_id, accounts, amount_a, amount_b)
-        }
-        TestProjectInstruction::RemoveLiquidity { lp_amount } => {
-            process_remove_liquidity(program_id, accounts, lp_amount)
-        }
-        TestProjectInstruction::Swap { amount_in, direction_a_to_b } => {
-            process_swap(program_id, accounts, amount_in, direction_a_to_b)
-        }
-        TestProjectInstruction::MultihopSwap { amount_in, minimum_amount_out } => {
-            process_multihop_swap(program_id, accounts, amount_in, minimum_amount_out)
-        }
-        TestProjectInstruction::MultihopSwapWithPath { amount_in, minimum_amount_out, token_path } => {
-            process_multihop_swap_with_path(program_id, accounts, amount_in, minimum_amount_out, token_path)
-        }
-        TestProjectInstruction::CollectFees { pool } => {
-            process_collect_fees(program_id, accounts, pool)
+fn is_native_sol_pair(token_a_info: &AccountInfo, token_b_info: &AccountInfo) -> bool {
+    *token_a_info.key == NATIVE_SOL_MINT || *token_b_info.key == NATIVE_SOL_MINT
+}
+
+struct InitPoolAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    token_a_info: &'a AccountInfo<'info>,
+    token_b_info: &'a AccountInfo<'info>,
+    vault_a_info: &'a AccountInfo<'info>,
+    vault_b_info: &'a AccountInfo<'info>,
+    lp_mint_info: &'a AccountInfo<'info>,
+    user_info: &'a AccountInfo<'info>,
+    user_token_a_info: &'a AccountInfo<'info>,
+    user_token_b_info: &'a AccountInfo<'info>,
+    user_lp_info: &'a AccountInfo<'info>,
+    token_program_info: &'a AccountInfo<'info>,
+    system_program_info: &'a AccountInfo<'info>,
+    rent_info: &'a AccountInfo<'info>,
+    locked_lp_info: &'a AccountInfo<'info>,
+}
+
+impl<'a, 'info> InitPoolAccounts<'a, 'info> {
+    fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let ctx = Self {
+            pool_info: next_account_info(account_info_iter)?,
+            token_a_info: next_account_info(account_info_iter)?,
+            token_b_info: next_account_info(account_info_iter)?,
+            vault_a_info: next_account_info(account_info_iter)?,
+            vault_b_info: next_account_info(account_info_iter)?,
+            lp_mint_info: next_account_info(account_info_iter)?,
+            user_info: next_account_info(account_info_iter)?,
+            user_token_a_info: next_account_info(account_info_iter)?,
+            user_token_b_info: next_account_info(account_info_iter)?,
+            user_lp_info: next_account_info(account_info_iter)?,
+            token_program_info: next_account_info(account_info_iter)?,
+            system_program_info: next_account_info(account_info_iter)?,
+            rent_info: next_account_info(account_info_iter)?,
+            locked_lp_info: next_account_info(account_info_iter)?,
+        };
+
+        check_user_wallet(ctx.user_info)?;
+        check_program_id(ctx.token_program_info, &gorbchain_token_program_id())?;
+        check_program_id(ctx.system_program_info, &solana_program::system_program::id())?;
+        check_program_id(ctx.rent_info, &solana_program::sysvar::rent::id())?;
+
+        // Pool, LP mint and vaults are created by this instruction
+        check_writable(ctx.pool_info)?;
+        check_writable(ctx.lp_mint_info)?;
+        check_writable(ctx.user_lp_info)?;
+        check_writable(ctx.locked_lp_info)?;
+        check_writable(ctx.vault_a_info)?;
+        check_writable(ctx.vault_b_info)?;
+
+        // The SOL side of a native pool is paid from the user wallet, not a token account,
+        // into the SOL vault passed as vault A
+        if is_native_sol_pair(ctx.token_a_info, ctx.token_b_info) {
+            check_token_account(ctx.user_token_b_info)?;
+        } else {
+            check_token_account(ctx.user_token_a_info)?;
+            check_token_account(ctx.user_token_b_info)?;
         }
-        TestProjectInstruction::WithdrawFees { pool, amount_a, amount_b } => {
-            process_withdraw_fees(program_id, accounts, pool, amount_a, amount_b)
+
+        Ok(ctx)
+    }
+}
+
+struct AddLiquidityAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    token_a_info: &'a AccountInfo<'info>,
+    token_b_info: &'a AccountInfo<'info>,
+    vault_a_info: &'a AccountInfo<'info>,
+    vault_b_info: &'a AccountInfo<'info>,
+    lp_mint_info: &'a AccountInfo<'info>,
+    user_token_a_info: &'a AccountInfo<'info>,
+    user_token_b_info: &'a AccountInfo<'info>,
+    user_lp_info: &'a AccountInfo<'info>,
+    user_info: &'a AccountInfo<'info>,
+    token_program_info: &'a AccountInfo<'info>,
+    // Only required by native SOL pools, which move lamports through the system program
+    system_program_info: Option<&'a AccountInfo<'info>>,
+}
+
+impl<'a, 'info> AddLiquidityAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let mut ctx = Self {
+            pool_info: next_account_info(account_info_iter)?,
+            token_a_info: next_account_info(account_info_iter)?,
+            token_b_info: next_account_info(account_info_iter)?,
+            vault_a_info: next_account_info(account_info_iter)?,
+            vault_b_info: next_account_info(account_info_iter)?,
+            lp_mint_info: next_account_info(account_info_iter)?,
+            user_token_a_info: next_account_info(account_info_iter)?,
+            user_token_b_info: next_account_info(account_info_iter)?,
+            user_lp_info: next_account_info(account_info_iter)?,
+            user_info: next_account_info(account_info_iter)?,
+            token_program_info: next_account_info(account_info_iter)?,
+            system_program_info: None,
+        };
+
+        check_user_wallet(ctx.user_info)?;
+        check_program_id(ctx.token_program_info, &gorbchain_token_program_id())?;
+        check_pool_account(ctx.pool_info, program_id)?;
+        check_token_account(ctx.lp_mint_info)?;
+        check_token_account(ctx.user_lp_info)?;
+
+        if is_native_sol_pair(ctx.token_a_info, ctx.token_b_info) {
+            let system_program_info = next_account_info(account_info_iter)?;
+            check_program_id(system_program_info, &solana_program::system_program::id())?;
+            ctx.system_program_info = Some(system_program_info);
+            check_owner(ctx.vault_a_info, &solana_program::system_program::id())?;
+            check_writable(ctx.vault_a_info)?;
+            check_token_account(ctx.vault_b_info)?;
+            check_token_account(ctx.user_token_b_info)?;
+        } else {
+            check_token_account(ctx.vault_a_info)?;
+            check_token_account(ctx.vault_b_info)?;
+            check_token_account(ctx.user_token_a_info)?;
+            check_token_account(ctx.user_token_b_info)?;
         }
-        TestProjectInstruction::SetFeeTreasury { pool, treasury } => {
-            process_set_fee_treasury(program_id, accounts, pool, treasury)
+
+        Ok(ctx)
+    }
+}
+
+struct RemoveLiquidityAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    token_a_info: &'a AccountInfo<'info>,
+    token_b_info: &'a AccountInfo<'info>,
+    vault_a_info: &'a AccountInfo<'info>,
+    vault_b_info: &'a AccountInfo<'info>,
+    lp_mint_info: &'a AccountInfo<'info>,
+    user_lp_info: &'a AccountInfo<'info>,
+    user_token_a_info: &'a AccountInfo<'info>,
+    user_token_b_info: &'a AccountInfo<'info>,
+    user_info: &'a AccountInfo<'info>,
+    token_program_info: &'a AccountInfo<'info>,
+    // Only required by native SOL pools, which pay SOL out of the vault through the system program
+    system_program_info: Option<&'a AccountInfo<'info>>,
+}
+
+impl<'a, 'info> RemoveLiquidityAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let mut ctx = Self {
+            pool_info: next_account_info(account_info_iter)?,
+            token_a_info: next_account_info(account_info_iter)?,
+            token_b_info: next_account_info(account_info_iter)?,
+            vault_a_info: next_account_info(account_info_iter)?,
+            vault_b_info: next_account_info(account_info_iter)?,
+            lp_mint_info: next_account_info(account_info_iter)?,
+            user_lp_info: next_account_info(account_info_iter)?,
+            user_token_a_info: next_account_info(account_info_iter)?,
+            user_token_b_info: next_account_info(account_info_iter)?,
+            user_info: next_account_info(account_info_iter)?,
+            token_program_info: next_account_info(account_info_iter)?,
+            system_program_info: None,
+        };
+
+        check_user_wallet(ctx.user_info)?;
+        check_program_id(ctx.token_program_info, &gorbchain_token_program_id())?;
+        check_pool_account(ctx.pool_info, program_id)?;
+        check_token_account(ctx.lp_mint_info)?;
+        check_token_account(ctx.user_lp_info)?;
+
+        if is_native_sol_pair(ctx.token_a_info, ctx.token_b_info) {
+            let system_program_info = next_account_info(account_info_iter)?;
+            check_program_id(system_program_info, &solana_program::system_program::id())?;
+            ctx.system_program_info = Some(system_program_info);
+            check_owner(ctx.vault_a_info, &solana_program::system_program::id())?;
+            check_writable(ctx.vault_a_info)?;
+            check_token_account(ctx.vault_b_info)?;
+            check_token_account(ctx.user_token_b_info)?;
+        } else {
+            check_token_account(ctx.vault_a_info)?;
+            check_token_account(ctx.vault_b_info)?;
+            check_token_account(ctx.user_token_a_info)?;
+            check_token_account(ctx.user_token_b_info)?;
         }
+
+        Ok(ctx)
     }
 }
 
-fn process_init_pool(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    amount_a: u64,
-    amount_b: u64,
-) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let pool_info = next_account_info(account_info_iter)?;
-    let token_a_info = next_account_info(account_info_iter)?;
-    let token_b_info = next_account_info(account_info_iter)?;
-    let vault_a_info = next_account_info(account_info_iter)?;
-    let vault_b_info = next_account_info(account_info_iter)?;
-    let lp_mint_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let user_token_a_info = next_account_info(account_info_iter)?;
-    let user_token_b_info = next_account_info(account_info_iter)?;
-    let user_lp_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
-    let system_program_info = next_account_info(account_info_iter)?;
-    let rent_info = next_account_info(account_info_iter)?;
+struct SwapAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    token_a_info: &'a AccountInfo<'info>,
+    token_b_info: &'a AccountInfo<'info>,
+    vault_a_info: &'a AccountInfo<'info>,
+    vault_b_info: &'a AccountInfo<'info>,
+    user_in_info: &'a AccountInfo<'info>,
+    user_out_info: &'a AccountInfo<'info>,
+    user_info: &'a AccountInfo<'info>,
+    token_program_info: &'a AccountInfo<'info>,
+    // Only required by native SOL pools, which move lamports through the system program
+    system_program_info: Option<&'a AccountInfo<'info>>,
+}
 
-    // Check if this is a native SOL pool initialization
-    let is_native_sol_pool = *token_a_info.key == NATIVE_SOL_MINT || *token_b_info.key == NATIVE_SOL_MINT;
-    
-    if is_native_sol_pool {
-        // Native SOL pool initialization logic
-        solana_program::log::sol_log("InitNativeSOLPool instruction called");
-        
-        // Determine which token is SOL and which is the SPL token
-        let (sol_amount, token_amount, token_mint) = if *token_a_info.key == NATIVE_SOL_MINT {
-            (amount_a, amount_b, *token_b_info.key)
-    } else {
-            (amount_b, amount_a, *token_a_info.key)
+impl<'a, 'info> SwapAccounts<'a, 'info> {
+    fn load(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'info>],
+        direction_a_to_b: bool,
+    ) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let mut ctx = Self {
+            pool_info: next_account_info(account_info_iter)?,
+            token_a_info: next_account_info(account_info_iter)?,
+            token_b_info: next_account_info(account_info_iter)?,
+            vault_a_info: next_account_info(account_info_iter)?,
+            vault_b_info: next_account_info(account_info_iter)?,
+            user_in_info: next_account_info(account_info_iter)?,
+            user_out_info: next_account_info(account_info_iter)?,
+            user_info: next_account_info(account_info_iter)?,
+            token_program_info: next_account_info(account_info_iter)?,
+            system_program_info: None,
         };
 
-        // Derive pool PDA for native SOL pool
-        let (pool_pda, _pool_bump) = Pubkey::find_program_address(
-            &[b"native_sol_pool", &token_mint.as_ref()],
-            program_id,
-        );
+        check_user_wallet(ctx.user_info)?;
+        check_program_id(ctx.token_program_info, &gorbchain_token_program_id())?;
+        check_pool_account(ctx.pool_info, program_id)?;
+
+        if is_native_sol_pair(ctx.token_a_info, ctx.token_b_info) {
+            let system_program_info = next_account_info(account_info_iter)?;
+            check_program_id(system_program_info, &solana_program::system_program::id())?;
+            ctx.system_program_info = Some(system_program_info);
+            check_owner(ctx.vault_a_info, &solana_program::system_program::id())?;
+            check_writable(ctx.vault_a_info)?;
+            check_token_account(ctx.vault_b_info)?;
+
+            // Only the token side of the trade goes through a token account
+            let sol_is_input = (*ctx.token_a_info.key == NATIVE_SOL_MINT) == direction_a_to_b;
+            if sol_is_input {
+                check_token_account(ctx.user_out_info)?;
+            } else {
+                check_token_account(ctx.user_in_info)?;
+            }
+        } else {
+            check_token_account(ctx.vault_a_info)?;
+            check_token_account(ctx.vault_b_info)?;
+            check_token_account(ctx.user_in_info)?;
+            check_token_account(ctx.user_out_info)?;
+        }
 
-        // Verify pool account
-        if pool_info.key != &pool_pda {
-        return Err(ProgramError::InvalidSeeds);
+        Ok(ctx)
     }
+}
 
-        // Derive LP mint PDA
-        let (lp_mint_pda, _lp_mint_bump) = Pubkey::find_program_address(
-            &[b"native_sol_lp_mint", pool_pda.as_ref()],
-            program_id,
-        );
+// One hop of a MultihopSwapRoute, as indices into the accounts that follow the
+// user, token program and source token account
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouteHopDescriptor {
+    pub pool: u8,
+    pub vault_a: u8,
+    pub vault_b: u8,
+}
 
-        // Verify LP mint
-        if lp_mint_info.key != &lp_mint_pda {
-            return Err(ProgramError::InvalidSeeds);
+// One leg of a SwapSplit: the share of the input it takes and the path it follows
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SplitRoute {
+    pub weight_bps: u16,
+    pub token_path: Vec<Pubkey>,
+}
+
+// Accounts of one hop as the route engine sees them
+#[derive(Clone, Copy)]
+struct RouteHop<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    vault_a_info: &'a AccountInfo<'info>,
+    vault_b_info: &'a AccountInfo<'info>,
+}
+
+// Only the user's source and final destination take part in a route, every hop
+// in between pays straight into the next pool's vault
+struct RouteAccounts<'a, 'info> {
+    user_info: &'a AccountInfo<'info>,
+    token_program_info: &'a AccountInfo<'info>,
+    source_info: &'a AccountInfo<'info>,
+    destination_info: &'a AccountInfo<'info>,
+    hops: Vec<RouteHop<'a, 'info>>,
+    // Only required when a hop goes through a native SOL pool
+    system_program_info: Option<&'a AccountInfo<'info>>,
+}
+
+impl<'a, 'info> RouteAccounts<'a, 'info> {
+    fn load(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'info>],
+        route: &[RouteHopDescriptor],
+        destination: u8,
+    ) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let user_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+
+        check_signer(user_info)?;
+        check_program_id(token_program_info, &gorbchain_token_program_id())?;
+        check_writable(source_info)?;
+
+        let hop_accounts = account_info_iter.as_slice();
+        let account_at = |index: u8| {
+            hop_accounts
+                .get(index as usize)
+                .ok_or(ProgramError::NotEnoughAccountKeys)
+        };
+        let destination_info = account_at(destination)?;
+        check_writable(destination_info)?;
+
+        let mut hops = Vec::with_capacity(route.len());
+        for descriptor in route {
+            let hop = RouteHop {
+                pool_info: account_at(descriptor.pool)?,
+                vault_a_info: account_at(descriptor.vault_a)?,
+                vault_b_info: account_at(descriptor.vault_b)?,
+            };
+
+            check_pool_account(hop.pool_info, program_id)?;
+            check_writable(hop.vault_a_info)?;
+            check_writable(hop.vault_b_info)?;
+
+            hops.push(hop);
         }
 
-        // Derive pool token vault PDA
-        let (pool_token_vault_pda, _pool_token_vault_bump) = Pubkey::find_program_address(
-            &[b"native_sol_vault", pool_pda.as_ref(), &token_mint.as_ref()],
-            program_id,
-        );
+        // Routes through native SOL pools pass the system program among the hop accounts
+        let system_program_info = hop_accounts
+            .iter()
+            .find(|info| *info.key == solana_program::system_program::id());
+
+        Ok(Self {
+            user_info,
+            token_program_info,
+            source_info,
+            destination_info,
+            hops,
+            system_program_info,
+        })
+    }
 
-        // Verify pool token vault
-        if vault_b_info.key != &pool_token_vault_pda {
-            return Err(ProgramError::InvalidSeeds);
+    // Compact layout shared by every path-based multihop instruction: user, token
+    // program, source, destination, then pool, vault A and vault B per hop
+    fn load_compact(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'info>],
+        num_hops: usize,
+    ) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let user_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+
+        check_signer(user_info)?;
+        check_program_id(token_program_info, &gorbchain_token_program_id())?;
+        check_writable(source_info)?;
+        check_writable(destination_info)?;
+
+        let mut hops = Vec::with_capacity(num_hops);
+        for _ in 0..num_hops {
+            let hop = RouteHop {
+                pool_info: next_account_info(account_info_iter)?,
+                vault_a_info: next_account_info(account_info_iter)?,
+                vault_b_info: next_account_info(account_info_iter)?,
+            };
+
+            check_pool_account(hop.pool_info, program_id)?;
+            check_writable(hop.vault_a_info)?;
+            check_writable(hop.vault_b_info)?;
+
+            hops.push(hop);
         }
 
-        // Check if pool account is already initialized
-        if !pool_info.data_is_empty() {
-            return Err(ProgramError::AccountAlreadyInitialized);
+        // Routes through native SOL pools pass the system program after the hops
+        let system_program_info = account_info_iter.find(|info| *info.key == solana_program::system_program::id());
+
+        Ok(Self {
+            user_info,
+            token_program_info,
+            source_info,
+            destination_info,
+            hops,
+            system_program_info,
+        })
+    }
+
+    // Route over a run of the loaded hops, between the same source and destination
+    fn route(&self, hops: std::ops::Range<usize>) -> Result<RouteAccounts<'a, 'info>, ProgramError> {
+        let route_hops = self.hops.get(hops).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if route_hops.is_empty() {
+            return Err(SwapError::InvalidSwapPath.into());
         }
 
-        let rent_sysvar = Rent::from_account_info(rent_info)?;
-        let pool_space = NativeSOLPool::LEN;
-        let vault_space = 165; // Token account size
-        let mint_space = 82; // Mint account size
-        let pool_lamports = rent_sysvar.minimum_balance(pool_space);
-        let vault_lamports = rent_sysvar.minimum_balance(vault_space);
-        let mint_lamports = rent_sysvar.minimum_balance(mint_space);
+        Ok(RouteAccounts {
+            user_info: self.user_info,
+            token_program_info: self.token_program_info,
+            source_info: self.source_info,
+            destination_info: self.destination_info,
+            hops: route_hops.to_vec(),
+            system_program_info: self.system_program_info,
+        })
+    }
+}
 
-        // Create pool account
-        let pool_signer_seeds: &[&[_]] = &[
-            b"native_sol_pool",
-            &token_mint.as_ref(),
-            &[_pool_bump],
-        ];
+// Accounts shared by the fee administration instructions
+struct FeeAdminAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    treasury_info: &'a AccountInfo<'info>,
+    authority_info: &'a AccountInfo<'info>,
+}
 
-        invoke_signed(
-            &system_instruction::create_account(
-                user_info.key,
-                pool_info.key,
-                pool_lamports + sol_amount, // Include SOL reserve
-                pool_space as u64,
-                program_id,
-            ),
-            &[
-                user_info.clone(),
-                pool_info.clone(),
-                system_program_info.clone(),
-            ],
-            &[pool_signer_seeds],
-        )?;
+impl<'a, 'info> FeeAdminAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let ctx = Self {
+            pool_info: next_account_info(account_info_iter)?,
+            treasury_info: next_account_info(account_info_iter)?,
+            authority_info: next_account_info(account_info_iter)?,
+        };
 
-        // Create pool token vault
-        let vault_signer_seeds: &[&[_]] = &[
-            b"native_sol_vault",
-            pool_pda.as_ref(),
-            &token_mint.as_ref(),
-            &[_pool_token_vault_bump],
-        ];
+        check_pool_account(ctx.pool_info, program_id)?;
+        check_signer(ctx.authority_info)?;
 
-        invoke_signed(
-            &system_instruction::create_account(
-                user_info.key,
-                vault_b_info.key,
-                vault_lamports,
-                vault_space as u64,
-                &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
-            ),
-            &[
-                user_info.clone(),
-                vault_b_info.clone(),
-                system_program_info.clone(),
-            ],
-            &[vault_signer_seeds],
-        )?;
+        Ok(ctx)
+    }
+}
 
-        // Initialize pool token vault as token account
-        invoke_signed(
-            &create_initialize_account_instruction(
-                vault_b_info.key,
-                &token_mint,
-                vault_b_info.key, // Vault is its own authority
-            ),
-            &[
-                vault_b_info.clone(),
-                token_b_info.clone(),
-                vault_b_info.clone(),
-                rent_info.clone(),
-            ],
-            &[vault_signer_seeds],
-        )?;
+struct InitConfigAccounts<'a, 'info> {
+    config_info: &'a AccountInfo<'info>,
+    admin_info: &'a AccountInfo<'info>,
+    program_data_info: &'a AccountInfo<'info>,
+    system_program_info: &'a AccountInfo<'info>,
+}
 
-        // Create LP mint account
-        let lp_mint_signer_seeds: &[&[_]] = &[
-            b"native_sol_lp_mint",
-            pool_pda.as_ref(),
-            &[_lp_mint_bump],
-        ];
+impl<'a, 'info> InitConfigAccounts<'a, 'info> {
+    fn load(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let ctx = Self {
+            config_info: next_account_info(account_info_iter)?,
+            admin_info: next_account_info(account_info_iter)?,
+            program_data_info: next_account_info(account_info_iter)?,
+            system_program_info: next_account_info(account_info_iter)?,
+        };
 
-        invoke_signed(
-            &system_instruction::create_account(
-                user_info.key,
-                lp_mint_info.key,
-                mint_lamports,
-                mint_space as u64,
-                &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
-            ),
-            &[
-                user_info.clone(),
-                lp_mint_info.clone(),
-                system_program_info.clone(),
-            ],
-            &[lp_mint_signer_seeds],
-        )?;
+        check_writable(ctx.config_info)?;
+        check_user_wallet(ctx.admin_info)?;
+        check_owner(ctx.program_data_info, &bpf_loader_upgradeable::id())?;
+        check_program_id(ctx.system_program_info, &solana_program::system_program::id())?;
 
-        // Initialize LP mint
-        invoke_signed(
-            &create_initialize_mint_instruction(
-                lp_mint_info.key,
-                0, // decimals (0 for LP token)
-                pool_info.key, // mint authority is pool
-                None,          // no freeze authority
-            ),
-            &[
-                lp_mint_info.clone(),
-                rent_info.clone(),
-            ],
-            &[lp_mint_signer_seeds],
-        )?;
+        Ok(ctx)
+    }
+}
 
-        // Transfer tokens to pool vault
-        invoke(
-            &create_transfer_instruction(
-                user_token_b_info.key,
-                vault_b_info.key,
-                user_info.key,
-                token_amount,
-            ),
-            &[
-                user_token_b_info.clone(),
-                vault_b_info.clone(),
-                user_info.clone(),
-                token_program_info.clone(),
-            ],
-        )?;
+// Accounts of the config admin instructions
+struct ConfigAdminAccounts<'a, 'info> {
+    config_info: &'a AccountInfo<'info>,
+    admin_info: &'a AccountInfo<'info>,
+}
 
-        // Create user LP ATA if it doesn't exist
-        invoke(
-            &create_associated_token_account_instruction(
-                user_info.key,
-                user_lp_info.key,
-                user_info.key,
-                lp_mint_info.key,
-            ),
-            &[
-                user_info.clone(),
-                user_lp_info.clone(),
-                user_info.clone(),
-                lp_mint_info.clone(),
-                rent_info.clone(),
-                token_program_info.clone(),
-                system_program_info.clone(),
-            ],
-        )?;
+impl<'a, 'info> ConfigAdminAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let ctx = Self {
+            config_info: next_account_info(account_info_iter)?,
+            admin_info: next_account_info(account_info_iter)?,
+        };
 
-        // Calculate initial liquidity (geometric mean)
-        let liquidity: u64 = (sol_amount as u128)
-            .checked_mul(token_amount as u128)
-            .unwrap()
-            .integer_sqrt() as u64;
+        check_pool_account(ctx.config_info, program_id)?;
+        check_signer(ctx.admin_info)?;
+        if *ctx.config_info.key != get_config_address(program_id).0 {
+            return Err(SwapError::InvalidConfig.into());
+        }
 
-        // Mint LP tokens to user
-        invoke_signed(
-            &create_mint_to_instruction(
-                lp_mint_info.key,
-                user_lp_info.key,
-                pool_info.key,
-                liquidity,
-            ),
-            &[
-                lp_mint_info.clone(),
-                user_lp_info.clone(),
-                pool_info.clone(),
-                token_program_info.clone(),
-            ],
-            &[pool_signer_seeds],
-        )?;
+        Ok(ctx)
+    }
+}
 
-        // Initialize pool state
-        let pool = NativeSOLPool {
-            token_a: NATIVE_SOL_MINT,
-            token_b: token_mint,
-            bump: _pool_bump,
-            reserve_a: sol_amount,
-            reserve_b: token_amount,
-            total_lp_supply: liquidity,
-            fee_collected_sol: 0,
-            fee_collected_token: 0,
-            fee_treasury: Pubkey::default(), // Will be set later via SetFeeTreasury
-            token_mint: token_mint,
+// Accounts of the admin-only pool instructions
+struct PoolAdminAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    config_info: &'a AccountInfo<'info>,
+    admin_info: &'a AccountInfo<'info>,
+}
+
+impl<'a, 'info> PoolAdminAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let ctx = Self {
+            pool_info: next_account_info(account_info_iter)?,
+            config_info: next_account_info(account_info_iter)?,
+            admin_info: next_account_info(account_info_iter)?,
         };
 
-        let mut pool_data = pool_info.try_borrow_mut_data()?;
-        if pool_data.len() < NativeSOLPool::LEN {
-            return Err(ProgramError::InvalidAccountData);
+        check_pool_account(ctx.pool_info, program_id)?;
+        check_owner(ctx.config_info, program_id)?;
+        check_signer(ctx.admin_info)?;
+        if *ctx.config_info.key != get_config_address(program_id).0 {
+            return Err(SwapError::InvalidConfig.into());
         }
-        pool.pack_into_slice(&mut pool_data[..NativeSOLPool::LEN]);
-
-        solana_program::log::sol_log(&format!("Pool initialized with {} SOL and {} tokens, {} LP tokens minted", sol_amount, token_amount, liquidity));
 
-        return Ok(());
+        Ok(ctx)
     }
+}
 
-    // Derive pool address and bump
-    let (pool_pubkey, _pool_bump) = Pubkey::find_program_address(
-        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-        program_id,
-    );
-    if pool_pubkey != *pool_info.key {
-        return Err(ProgramError::InvalidSeeds);
+// Accounts of the pool authority transfer instructions
+struct PoolAuthorityAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    authority_info: &'a AccountInfo<'info>,
+}
+
+impl<'a, 'info> PoolAuthorityAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let ctx = Self {
+            pool_info: next_account_info(account_info_iter)?,
+            authority_info: next_account_info(account_info_iter)?,
+        };
+
+        check_pool_account(ctx.pool_info, program_id)?;
+        check_signer(ctx.authority_info)?;
+
+        Ok(ctx)
     }
+}
 
-    // Derive vault addresses and verify them
-    let (vault_a_pubkey, vault_a_bump) = get_vault_address(&pool_pubkey, token_a_info.key, program_id);
-    let (vault_b_pubkey, vault_b_bump) = get_vault_address(&pool_pubkey, token_b_info.key, program_id);
-    
-    if vault_a_pubkey != *vault_a_info.key {
-        return Err(ProgramError::InvalidSeeds);
+struct CollectFeesAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    treasury_info: &'a AccountInfo<'info>,
+    authority_info: &'a AccountInfo<'info>,
+    token_program_info: &'a AccountInfo<'info>,
+    // Regular pool: vault A, vault B, treasury token A, treasury token B
+    // Native SOL pool: token vault, treasury token account, SOL vault, system program
+    // Validated by the handler once the pool type is known
+    fee_accounts: &'a [AccountInfo<'info>],
+}
+
+impl<'a, 'info> CollectFeesAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        if accounts.len() < 4 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let ctx = Self {
+            pool_info: &accounts[0],
+            treasury_info: &accounts[1],
+            authority_info: &accounts[2],
+            token_program_info: &accounts[3],
+            fee_accounts: &accounts[4..],
+        };
+
+        check_pool_account(ctx.pool_info, program_id)?;
+        check_writable(ctx.treasury_info)?;
+        check_signer(ctx.authority_info)?;
+        check_program_id(ctx.token_program_info, &gorbchain_token_program_id())?;
+
+        Ok(ctx)
     }
-    if vault_b_pubkey != *vault_b_info.key {
-        return Err(ProgramError::InvalidSeeds);
+}
+
+struct SyncAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    // Regular pool: vault A, vault B
+    // Native SOL pool: SOL vault, token vault
+    vault_accounts: &'a [AccountInfo<'info>],
+}
+
+impl<'a, 'info> SyncAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        if accounts.len() < 2 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let ctx = Self {
+            pool_info: &accounts[0],
+            vault_accounts: &accounts[1..],
+        };
+
+        check_pool_account(ctx.pool_info, program_id)?;
+        for vault_info in ctx.vault_accounts {
+            check_vault_owner(vault_info)?;
+        }
+
+        Ok(ctx)
     }
+}
 
-    let rent = Rent::from_account_info(rent_info)?;
-    let pool_space = Pool::LEN;
-    let vault_space = 165; // Token account size
-    let pool_lamports = rent.minimum_balance(pool_space);
-    let vault_lamports = rent.minimum_balance(vault_space);
+struct SkimAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    token_program_info: &'a AccountInfo<'info>,
+    system_program_info: &'a AccountInfo<'info>,
+    // Regular pool: vault A, vault B, recipient token A, recipient token B
+    // Native SOL pool: SOL vault, token vault, recipient wallet, recipient token account
+    skim_accounts: &'a [AccountInfo<'info>],
+}
 
-    // Create pool account
-    let pool_signer_seeds: &[&[_]] = &[
-            b"pool",
-            token_a_info.key.as_ref(),
-            token_b_info.key.as_ref(),
-            &[_pool_bump],
-    ];
+impl<'a, 'info> SkimAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        if accounts.len() < 3 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let ctx = Self {
+            pool_info: &accounts[0],
+            token_program_info: &accounts[1],
+            system_program_info: &accounts[2],
+            skim_accounts: &accounts[3..],
+        };
 
-    invoke_signed(
-        &system_instruction::create_account(
-            user_info.key,
-            pool_info.key,
-            pool_lamports,
-            pool_space as u64,
-            program_id,
-        ),
-        &[
-            user_info.clone(),
-            pool_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+        check_pool_account(ctx.pool_info, program_id)?;
+        check_program_id(ctx.token_program_info, &gorbchain_token_program_id())?;
+        check_program_id(ctx.system_program_info, &solana_program::system_program::id())?;
+        for skim_account_info in ctx.skim_accounts {
+            check_writable(skim_account_info)?;
+        }
 
-    // Create vault A as PDA
-    let vault_a_signer_seeds: &[&[_]] = &[
-            b"vault",
-            pool_info.key.as_ref(),
-            token_a_info.key.as_ref(),
-            &[vault_a_bump],
-    ];
+        Ok(ctx)
+    }
+}
 
-    invoke_signed(
-        &system_instruction::create_account(
-            user_info.key,
-            vault_a_info.key,
-            vault_lamports,
-            vault_space as u64,
-            &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
-        ),
-        &[
-            user_info.clone(),
-            vault_a_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[vault_a_signer_seeds],
-    )?;
+struct MigratePoolAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    config_info: &'a AccountInfo<'info>,
+    admin_info: &'a AccountInfo<'info>,
+    system_program_info: &'a AccountInfo<'info>,
+    // Only required by legacy native SOL pools, whose SOL moves into the SOL vault
+    sol_vault_info: Option<&'a AccountInfo<'info>>,
+}
 
-    // Create vault B as PDA
-        let vault_b_signer_seeds: &[&[_]] = &[
-            b"vault",
-            pool_info.key.as_ref(),
-            token_b_info.key.as_ref(),
-            &[vault_b_bump],
-        ];
+impl<'a, 'info> MigratePoolAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let ctx = Self {
+            pool_info: next_account_info(account_info_iter)?,
+            config_info: next_account_info(account_info_iter)?,
+            admin_info: next_account_info(account_info_iter)?,
+            system_program_info: next_account_info(account_info_iter)?,
+            sol_vault_info: account_info_iter.next(),
+        };
 
-        invoke_signed(
+        check_pool_account(ctx.pool_info, program_id)?;
+        check_owner(ctx.config_info, program_id)?;
+        check_user_wallet(ctx.admin_info)?;
+        check_program_id(ctx.system_program_info, &solana_program::system_program::id())?;
+        if *ctx.config_info.key != get_config_address(program_id).0 {
+            return Err(SwapError::InvalidConfig.into());
+        }
+        if let Some(sol_vault_info) = ctx.sol_vault_info {
+            check_writable(sol_vault_info)?;
+        }
+
+        Ok(ctx)
+    }
+}
+
+struct InitObservationsAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    observation_info: &'a AccountInfo<'info>,
+    payer_info: &'a AccountInfo<'info>,
+    system_program_info: &'a AccountInfo<'info>,
+}
+
+impl<'a, 'info> InitObservationsAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let ctx = Self {
+            pool_info: next_account_info(account_info_iter)?,
+            observation_info: next_account_info(account_info_iter)?,
+            payer_info: next_account_info(account_info_iter)?,
+            system_program_info: next_account_info(account_info_iter)?,
+        };
+
+        check_owner(ctx.pool_info, program_id)?;
+        check_writable(ctx.observation_info)?;
+        check_user_wallet(ctx.payer_info)?;
+        check_program_id(ctx.system_program_info, &solana_program::system_program::id())?;
+
+        Ok(ctx)
+    }
+}
+
+struct RecordObservationAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    observation_info: &'a AccountInfo<'info>,
+}
+
+impl<'a, 'info> RecordObservationAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let ctx = Self {
+            pool_info: next_account_info(account_info_iter)?,
+            observation_info: next_account_info(account_info_iter)?,
+        };
+
+        check_owner(ctx.pool_info, program_id)?;
+        check_writable(ctx.observation_info)?;
+        check_owner(ctx.observation_info, program_id)?;
+
+        Ok(ctx)
+    }
+}
+
+struct WithdrawFeesAccounts<'a, 'info> {
+    pool_info: &'a AccountInfo<'info>,
+    treasury_info: &'a AccountInfo<'info>,
+    authority_info: &'a AccountInfo<'info>,
+    token_program_info: &'a AccountInfo<'info>,
+    system_program_info: &'a AccountInfo<'info>,
+    // Vault accounts for the requested withdrawals, validated by the handler
+    // against the pool's vault PDAs
+    vault_accounts: &'a [AccountInfo<'info>],
+}
+
+impl<'a, 'info> WithdrawFeesAccounts<'a, 'info> {
+    fn load(program_id: &Pubkey, accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        if accounts.len() < 5 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let ctx = Self {
+            pool_info: &accounts[0],
+            treasury_info: &accounts[1],
+            authority_info: &accounts[2],
+            token_program_info: &accounts[3],
+            system_program_info: &accounts[4],
+            vault_accounts: &accounts[5..],
+        };
+
+        check_pool_account(ctx.pool_info, program_id)?;
+        check_writable(ctx.treasury_info)?;
+        check_signer(ctx.authority_info)?;
+        check_program_id(ctx.token_program_info, &gorbchain_token_program_id())?;
+        check_program_id(ctx.system_program_info, &solana_program::system_program::id())?;
+        for vault_info in ctx.vault_accounts {
+            check_vault_owner(vault_info)?;
+        }
+
+        Ok(ctx)
+    }
+}
+
+// Entry point
+entrypoint!(process_instruction);
+
+// Instructions
+#[derive(BorshSerialize, BorshDeserialize, Debug, ShankInstruction)]
+pub enum TestProjectInstruction {
+    InitPool { amount_a: u64, amount_b: u64 },
+    AddLiquidity { amount_a: u64, amount_b: u64 },
+    RemoveLiquidity { lp_amount: u64 },
+    Swap { amount_in: u64, direction_a_to_b: bool },
+    MultihopSwap { amount_in: u64, minimum_amount_out: u64 },
+    MultihopSwapWithPath { amount_in: u64, minimum_amount_out: u64, token_path: Vec<Pubkey> },
+    CollectFees { pool: Pubkey },
+    WithdrawFees { pool: Pubkey, amount_a: u64, amount_b: u64 },
+    SetFeeTreasury { pool: Pubkey, treasury: Pubkey },
+    SwapWithSlippage { amount_in: u64, minimum_amount_out: u64, max_price_impact_bps: Option<u16>, direction_a_to_b: bool, valid_until: Option<Deadline> },
+    SwapExactOut { amount_out: u64, maximum_amount_in: u64, direction_a_to_b: bool, valid_until: Option<Deadline> },
+    MultihopSwapExactOutWithPath { amount_out: u64, maximum_amount_in: u64, token_path: Vec<Pubkey>, valid_until: Option<Deadline> },
+    AddLiquidityV2 { amount_a: u64, amount_b: u64, min_lp_out: u64, valid_until: Option<Deadline> },
+    RemoveLiquidityV2 { lp_amount: u64, min_amount_a: u64, min_amount_b: u64, valid_until: Option<Deadline> },
+    MultihopSwapV2 { amount_in: u64, minimum_amount_out: u64, valid_until: Option<Deadline> },
+    MultihopSwapWithPathV2 { amount_in: u64, minimum_amount_out: u64, token_path: Vec<Pubkey>, valid_until: Option<Deadline> },
+    ProposePoolAuthority { pool: Pubkey, new_authority: Pubkey },
+    AcceptPoolAuthority { pool: Pubkey },
+    InitPoolV2 { amount_a: u64, amount_b: u64, fee_bps: u16 },
+    SetProtocolFee { pool: Pubkey, protocol_fee_divisor: u8 },
+    InitConfig { default_fee_bps: u16, protocol_fee_divisor: u8, default_treasury: Pubkey },
+    UpdateConfig { default_fee_bps: u16, protocol_fee_divisor: u8, default_treasury: Pubkey, paused: bool, new_admin: Option<Pubkey> },
+    PausePool { pool: Pubkey },
+    UnpausePool { pool: Pubkey },
+    Sync { pool: Pubkey },
+    Skim { pool: Pubkey },
+    MigratePool { pool: Pubkey },
+    MultihopSwapRoute { amount_in: u64, minimum_amount_out: u64, route: Vec<RouteHopDescriptor>, destination: u8, valid_until: Option<Deadline> },
+    SwapSplit { amount_in: u64, minimum_amount_out: u64, routes: Vec<SplitRoute>, valid_until: Option<Deadline> },
+    MultihopSwapCompact { amount_in: u64, minimum_amount_out: u64, token_path: Vec<Pubkey>, valid_until: Option<Deadline> },
+    InitObservations { pool: Pubkey },
+    RecordObservation { pool: Pubkey },
+}
+
+// Last moment at which a transaction may still execute, checked against the Clock sysvar
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy)]
+pub enum Deadline {
+    UnixTimestamp(i64),
+    Slot(u64),
+}
+
+impl Deadline {
+    // The deadline itself is still valid, only a later timestamp or slot is past it
+    fn has_passed(&self, clock: &Clock) -> bool {
+        match *self {
+            Deadline::UnixTimestamp(timestamp) => clock.unix_timestamp > timestamp,
+            Deadline::Slot(slot) => clock.slot > slot,
+        }
+    }
+}
+
+// Record of a CollectFees payout, emitted with sol_log_data (borsh encoded).
+// For native SOL pools amount_a is lamports and amount_b is the SPL token.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct FeesCollected {
+    pub pool: Pubkey,
+    pub treasury: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+}
+
+// How the caller fixed the amounts of a single-hop swap
+#[derive(Clone, Copy, Debug)]
+enum SwapAmount {
+    // Spend exactly amount_in, receive at least minimum_amount_out
+    ExactIn { amount_in: u64, minimum_amount_out: u64, max_price_impact_bps: Option<u16> },
+    // Receive exactly amount_out, spend at most maximum_amount_in
+    ExactOut { amount_out: u64, maximum_amount_in: u64 },
+}
+
+impl SwapAmount {
+    // Resolve to the (amount_in, amount_out) pair to execute against the given reserves
+    fn resolve(&self, reserve_in: u64, reserve_out: u64, fee_bps: u16) -> Result<(u64, u64), ProgramError> {
+        match *self {
+            SwapAmount::ExactIn { amount_in, minimum_amount_out, max_price_impact_bps } => {
+                let amount_out = calculate_swap_output(amount_in, reserve_in, reserve_out, fee_bps)?;
+                check_swap_limits(amount_in, amount_out, reserve_in, reserve_out, minimum_amount_out, max_price_impact_bps)?;
+                Ok((amount_in, amount_out))
+            }
+            SwapAmount::ExactOut { amount_out, maximum_amount_in } => {
+                let amount_in = calculate_swap_input(amount_out, reserve_in, reserve_out, fee_bps)?;
+                if amount_in > maximum_amount_in {
+                    solana_program::log::sol_log(&format!("Slippage exceeded: {} > maximum {}", amount_in, maximum_amount_in));
+                    return Err(SwapError::SlippageExceeded.into());
+                }
+                Ok((amount_in, amount_out))
+            }
+        }
+    }
+}
+
+// Paused pools reject swaps and deposits but still allow RemoveLiquidity
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolStatus {
+    Active,
+    Paused,
+}
+
+// Discriminator stored in the first byte of every program account and checked
+// on every load. Values are part of the account layout: never reorder, only append.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountType {
+    Uninitialized,
+    Pool,
+    NativeSOLPool,
+    Config,
+    ObservationBuffer,
+}
+
+// Layout version of Pool and NativeSOLPool, bumped whenever their fields change.
+// New fields are carved out of the reserved bytes and filled in by MigratePool.
+// Version 2 added the cumulative prices.
+const POOL_VERSION: u8 = 2;
+const POOL_RESERVED_LEN: usize = 24;
+
+fn check_pool_version(version: u8) -> ProgramResult {
+    if version != POOL_VERSION {
+        solana_program::log::sol_log(&format!("Pool version {} needs migration to {}", version, POOL_VERSION));
+        return Err(SwapError::PoolNeedsMigration.into());
+    }
+    Ok(())
+}
+
+impl AccountType {
+    // Read the discriminator of an account without deserializing the rest
+    fn of(info: &AccountInfo) -> Result<AccountType, ProgramError> {
+        let data = info.data.borrow();
+        let discriminator = data.get(..1).ok_or(ProgramError::InvalidAccountData)?;
+        AccountType::try_from_slice(discriminator).map_err(|_| SwapError::InvalidAccountType.into())
+    }
+
+    fn check(self, expected: AccountType) -> ProgramResult {
+        if self != expected {
+            solana_program::log::sol_log(&format!("Invalid account type: expected {:?}, got {:?}", expected, self));
+            return Err(SwapError::InvalidAccountType.into());
+        }
+        Ok(())
+    }
+}
+
+// Pool state
+#[derive(BorshSerialize, BorshDeserialize, Debug, ShankAccount)]
+pub struct Pool {
+    pub account_type: AccountType, // AccountType::Pool
+    pub version: u8,               // POOL_VERSION
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub bump: u8,
+    pub reserve_a: u64,
+    pub reserve_b: u64,
+    pub total_lp_supply: u64,
+    pub fee_collected_a: u64,
+    pub fee_collected_b: u64,
+    pub fee_treasury: Pubkey,
+    pub authority: Pubkey,         // Signs fee administration, set to the config admin at InitPool
+    pub pending_authority: Pubkey, // Proposed new authority, Pubkey::default() when none
+    pub fee_bps: u16,              // Swap fee in basis points, one of FEE_TIERS_BPS
+    pub protocol_fee_divisor: u8,  // Protocol takes 1/divisor of the swap fee, 0 = off
+    pub status: PoolStatus,
+    pub price_a_cumulative: u128,  // Time-weighted price of A in B (Q64.64 per second), wraps on overflow
+    pub price_b_cumulative: u128,  // Time-weighted price of B in A (Q64.64 per second), wraps on overflow
+    pub last_update_timestamp: i64, // Clock::unix_timestamp the cumulative prices were last advanced to
+    pub reserved: [u8; 24],        // POOL_RESERVED_LEN zeroed bytes for fields of later versions
+}
+
+impl Sealed for Pool {}
+
+impl IsInitialized for Pool {
+    fn is_initialized(&self) -> bool {
+        self.token_a != Pubkey::default()
+    }
+}
+
+impl Pack for Pool {
+    const LEN: usize = 1 + 1 + 32 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 32 + 32 + 32 + 2 + 1 + 1 + 16 + 16 + 8 + POOL_RESERVED_LEN; // 271 bytes
+    
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let pool = Pool::try_from_slice(src)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        pool.account_type.check(AccountType::Pool)?;
+        check_pool_version(pool.version)?;
+        Ok(pool)
+    }
+    
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let data = self.try_to_vec().unwrap();
+        dst[..data.len()].copy_from_slice(&data);
+    }
+}
+
+impl Pool {
+    // Credit a swap to the reserves. The protocol share of the fee is accrued in
+    // fee_collected_* and kept out of the reserves; the LP share compounds in them.
+    // Returns the protocol fee.
+    fn apply_swap(&mut self, direction_a_to_b: bool, amount_in: u64, amount_out: u64) -> Result<u64, ProgramError> {
+        let (reserve_credit, protocol_fee) = split_protocol_fee(amount_in, self.fee_bps, self.protocol_fee_divisor)?;
+        if direction_a_to_b {
+            self.reserve_a = math::add(self.reserve_a, reserve_credit)?;
+            self.reserve_b = math::sub(self.reserve_b, amount_out)?;
+            self.fee_collected_a = math::add(self.fee_collected_a, protocol_fee)?;
+        } else {
+            self.reserve_b = math::add(self.reserve_b, reserve_credit)?;
+            self.reserve_a = math::sub(self.reserve_a, amount_out)?;
+            self.fee_collected_b = math::add(self.fee_collected_b, protocol_fee)?;
+        }
+        Ok(protocol_fee)
+    }
+
+    fn check_active(&self) -> ProgramResult {
+        check_pool_status(self.status)
+    }
+
+    // Invariant: each vault holds its reserve plus the unclaimed protocol fees
+    fn check_vault_balances(&self, vault_a_info: &AccountInfo, vault_b_info: &AccountInfo) -> ProgramResult {
+        check_vault_balance(vault_a_info, self.reserve_a, self.fee_collected_a)?;
+        check_vault_balance(vault_b_info, self.reserve_b, self.fee_collected_b)
+    }
+
+    // Realign the reserves with the vault balances, so tokens sent straight to a
+    // vault count towards the reserves instead of being ignored by the curve
+    fn reconcile(&mut self, vault_a_info: &AccountInfo, vault_b_info: &AccountInfo) -> ProgramResult {
+        self.reserve_a = reserve_from_balance(vault_balance(vault_a_info)?, self.fee_collected_a)?;
+        self.reserve_b = reserve_from_balance(vault_balance(vault_b_info)?, self.fee_collected_b)?;
+        Ok(())
+    }
+
+    fn price_cumulative_at(&self, now: i64) -> (u128, u128) {
+        accumulate_prices(
+            (self.price_a_cumulative, self.price_b_cumulative),
+            self.last_update_timestamp,
+            self.reserve_a,
+            self.reserve_b,
+            now,
+        )
+    }
+
+    // Advance the cumulative prices to now. Runs before the reserves change, so the
+    // time since the last update is weighted at the price those reserves held.
+    fn update_price_cumulative(&mut self) -> ProgramResult {
+        let now = Clock::get()?.unix_timestamp;
+        (self.price_a_cumulative, self.price_b_cumulative) = self.price_cumulative_at(now);
+        self.last_update_timestamp = self.last_update_timestamp.max(now);
+        Ok(())
+    }
+}
+
+fn check_pool_status(status: PoolStatus) -> ProgramResult {
+    if status == PoolStatus::Paused {
+        solana_program::log::sol_log("Pool is paused");
+        return Err(SwapError::PoolPaused.into());
+    }
+    Ok(())
+}
+
+// Native SOL Pool state
+#[derive(BorshSerialize, BorshDeserialize, Debug, ShankAccount)]
+pub struct NativeSOLPool {
+    pub account_type: AccountType, // AccountType::NativeSOLPool
+    pub version: u8,               // POOL_VERSION
+    pub token_a: Pubkey,     // Native SOL address (So11111111111111111111111111111111111111112)
+    pub token_b: Pubkey,     // The SPL token mint
+    pub bump: u8,
+    pub reserve_a: u64,      // Native SOL reserve
+    pub reserve_b: u64,      // SPL token reserve
+    pub total_lp_supply: u64,
+    pub fee_collected_sol: u64,
+    pub fee_collected_token: u64,
+    pub fee_treasury: Pubkey,
+    pub token_mint: Pubkey,  // The SPL token mint (same as token_b for convenience)
+    pub authority: Pubkey,         // Signs fee administration, set to the config admin at InitPool
+    pub pending_authority: Pubkey, // Proposed new authority, Pubkey::default() when none
+    pub fee_bps: u16,              // Swap fee in basis points, one of FEE_TIERS_BPS
+    pub protocol_fee_divisor: u8,  // Protocol takes 1/divisor of the swap fee, 0 = off
+    pub status: PoolStatus,
+    pub price_a_cumulative: u128,  // Time-weighted price of A in B (Q64.64 per second), wraps on overflow
+    pub price_b_cumulative: u128,  // Time-weighted price of B in A (Q64.64 per second), wraps on overflow
+    pub last_update_timestamp: i64, // Clock::unix_timestamp the cumulative prices were last advanced to
+    pub reserved: [u8; 24],        // POOL_RESERVED_LEN zeroed bytes for fields of later versions
+}
+
+impl Sealed for NativeSOLPool {}
+
+impl IsInitialized for NativeSOLPool {
+    fn is_initialized(&self) -> bool {
+        self.token_a != Pubkey::default()
+    }
+}
+
+impl Pack for NativeSOLPool {
+    const LEN: usize = 1 + 1 + 32 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 32 + 32 + 32 + 32 + 2 + 1 + 1 + 16 + 16 + 8 + POOL_RESERVED_LEN; // 303 bytes
+    
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let pool = NativeSOLPool::try_from_slice(src)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        pool.account_type.check(AccountType::NativeSOLPool)?;
+        check_pool_version(pool.version)?;
+        Ok(pool)
+    }
+    
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let data = self.try_to_vec().unwrap();
+        dst[..data.len()].copy_from_slice(&data);
+    }
+}
+
+impl NativeSOLPool {
+    // Same as Pool::apply_swap, with SOL as token A
+    fn apply_swap(&mut self, sol_to_token: bool, amount_in: u64, amount_out: u64) -> Result<u64, ProgramError> {
+        let (reserve_credit, protocol_fee) = split_protocol_fee(amount_in, self.fee_bps, self.protocol_fee_divisor)?;
+        if sol_to_token {
+            self.reserve_a = math::add(self.reserve_a, reserve_credit)?;
+            self.reserve_b = math::sub(self.reserve_b, amount_out)?;
+            self.fee_collected_sol = math::add(self.fee_collected_sol, protocol_fee)?;
+        } else {
+            self.reserve_b = math::add(self.reserve_b, reserve_credit)?;
+            self.reserve_a = math::sub(self.reserve_a, amount_out)?;
+            self.fee_collected_token = math::add(self.fee_collected_token, protocol_fee)?;
+        }
+        Ok(protocol_fee)
+    }
+
+    fn check_active(&self) -> ProgramResult {
+        check_pool_status(self.status)
+    }
+
+    // Invariant: the SOL vault holds the SOL reserve plus unclaimed SOL fees on top of
+    // its rent-exempt minimum, the token vault the token reserve plus unclaimed token fees
+    fn check_balances(&self, sol_vault_info: &AccountInfo, vault_info: &AccountInfo) -> ProgramResult {
+        let balance = sol_vault_balance(sol_vault_info)?;
+        let required = math::add(self.reserve_a, self.fee_collected_sol)?;
+        if balance < required {
+            solana_program::log::sol_log(&format!("SOL vault balance {} below reserve + fees {}", balance, required));
+            return Err(SwapError::VaultBalanceMismatch.into());
+        }
+        check_vault_balance(vault_info, self.reserve_b, self.fee_collected_token)
+    }
+
+    // Same as Pool::reconcile, with the SOL reserve read from the SOL vault
+    fn reconcile(&mut self, sol_vault_info: &AccountInfo, vault_info: &AccountInfo) -> ProgramResult {
+        self.reserve_a = reserve_from_balance(sol_vault_balance(sol_vault_info)?, self.fee_collected_sol)?;
+        self.reserve_b = reserve_from_balance(vault_balance(vault_info)?, self.fee_collected_token)?;
+        Ok(())
+    }
+
+    // Same as Pool::price_cumulative_at, with SOL as token A
+    fn price_cumulative_at(&self, now: i64) -> (u128, u128) {
+        accumulate_prices(
+            (self.price_a_cumulative, self.price_b_cumulative),
+            self.last_update_timestamp,
+            self.reserve_a,
+            self.reserve_b,
+            now,
+        )
+    }
+
+    fn update_price_cumulative(&mut self) -> ProgramResult {
+        let now = Clock::get()?.unix_timestamp;
+        (self.price_a_cumulative, self.price_b_cumulative) = self.price_cumulative_at(now);
+        self.last_update_timestamp = self.last_update_timestamp.max(now);
+        Ok(())
+    }
+}
+
+// Spot price of each token in the other as Q64.64 fixed point. Reserves fit in
+// 64 bits, so the shifted numerator cannot overflow u128.
+fn spot_prices_q64(reserve_a: u64, reserve_b: u64) -> (u128, u128) {
+    if reserve_a == 0 || reserve_b == 0 {
+        return (0, 0);
+    }
+    (
+        ((reserve_b as u128) << 64) / reserve_a as u128,
+        ((reserve_a as u128) << 64) / reserve_b as u128,
+    )
+}
+
+// Cumulative prices advanced from last_update_timestamp to now at the current
+// spot price. The accumulators wrap on overflow on purpose: consumers only use the
+// wrapping difference between two observations, which stays exact.
+fn accumulate_prices(
+    (price_a_cumulative, price_b_cumulative): (u128, u128),
+    last_update_timestamp: i64,
+    reserve_a: u64,
+    reserve_b: u64,
+    now: i64,
+) -> (u128, u128) {
+    let elapsed = now.saturating_sub(last_update_timestamp).max(0) as u128;
+    let (price_a, price_b) = spot_prices_q64(reserve_a, reserve_b);
+    (
+        price_a_cumulative.wrapping_add(price_a.wrapping_mul(elapsed)),
+        price_b_cumulative.wrapping_add(price_b.wrapping_mul(elapsed)),
+    )
+}
+
+// SOL vault of a native pool: a data-less, system-owned PDA holding the SOL reserve
+// and SOL fees, kept apart from the rent of the pool state account
+fn get_sol_vault_address(pool: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"native_sol_lamport_vault", pool.as_ref()], program_id)
+}
+
+// Lamports held by the SOL vault above its rent-exempt minimum
+fn sol_vault_balance(sol_vault_info: &AccountInfo) -> Result<u64, ProgramError> {
+    let rent_exempt_lamports = Rent::get()?.minimum_balance(0);
+    reserve_from_balance(sol_vault_info.lamports(), rent_exempt_lamports)
+}
+
+// Pay SOL out of the vault. The vault is owned by the system program, so the
+// transfer goes through it with the vault PDA signing.
+fn transfer_from_sol_vault<'info>(
+    pool: &Pubkey,
+    sol_vault_info: &AccountInfo<'info>,
+    sol_vault_bump: u8,
+    to_info: &AccountInfo<'info>,
+    system_program_info: &AccountInfo<'info>,
+    amount: u64,
+) -> ProgramResult {
+    invoke_signed(
+        &system_instruction::transfer(sol_vault_info.key, to_info.key, amount),
+        &[
+            sol_vault_info.clone(),
+            to_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&[b"native_sol_lamport_vault", pool.as_ref(), &[sol_vault_bump]]],
+    )
+}
+
+// The SOL leg of a native pool instruction may be a wSOL token account instead of
+// the user's wallet. Any other account in that slot means plain lamports.
+fn is_wrapped_sol_account(info: &AccountInfo) -> bool {
+    *info.owner == gorbchain_token_program_id()
+        && unpack_token_account(info).is_ok_and(|account| account.mint == NATIVE_SOL_MINT)
+}
+
+// Where SOL paid out of a native pool lands: the wSOL account if one was passed, else the wallet
+fn sol_destination<'a, 'info>(
+    sol_account_info: &'a AccountInfo<'info>,
+    user_info: &'a AccountInfo<'info>,
+) -> &'a AccountInfo<'info> {
+    if is_wrapped_sol_account(sol_account_info) {
+        sol_account_info
+    } else {
+        user_info
+    }
+}
+
+// Unwrap a wSOL account holding at least amount by closing it into the wallet
+fn unwrap_sol<'info>(
+    wsol_info: &AccountInfo<'info>,
+    user_info: &AccountInfo<'info>,
+    token_program_info: &AccountInfo<'info>,
+    amount: u64,
+) -> ProgramResult {
+    check_writable(wsol_info)?;
+    
+    // Count lamports sent straight to the account before checking its balance
+    invoke(
+        &create_sync_native_instruction(wsol_info.key),
+        &[wsol_info.clone(), token_program_info.clone()],
+    )?;
+    let wrapped_amount = unpack_token_account(wsol_info)?.amount;
+    if wrapped_amount < amount {
+        solana_program::log::sol_log(&format!("wSOL balance {} below {}", wrapped_amount, amount));
+        return Err(SwapError::InsufficientWrappedSol.into());
+    }
+    
+    invoke(
+        &create_close_account_instruction(wsol_info.key, user_info.key, user_info.key),
+        &[
+            wsol_info.clone(),
+            user_info.clone(),
+            user_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+// Pay SOL from the user into the SOL vault. A wSOL source is unwrapped first by
+// closing it into the wallet, so whatever is not deposited stays with the user as SOL.
+fn deposit_sol<'info>(
+    sol_source_info: &AccountInfo<'info>,
+    user_info: &AccountInfo<'info>,
+    sol_vault_info: &AccountInfo<'info>,
+    token_program_info: &AccountInfo<'info>,
+    system_program_info: &AccountInfo<'info>,
+    amount: u64,
+) -> ProgramResult {
+    if is_wrapped_sol_account(sol_source_info) {
+        unwrap_sol(sol_source_info, user_info, token_program_info, amount)?;
+    }
+    
+    invoke(
+        &system_instruction::transfer(user_info.key, sol_vault_info.key, amount),
+        &[
+            user_info.clone(),
+            sol_vault_info.clone(),
+            system_program_info.clone(),
+        ],
+    )
+}
+
+// Pay SOL out of the vault. A wSOL destination is credited in lamports and then
+// synced so its token balance reflects them.
+fn withdraw_sol<'info>(
+    pool: &Pubkey,
+    sol_vault_info: &AccountInfo<'info>,
+    sol_vault_bump: u8,
+    destination_info: &AccountInfo<'info>,
+    token_program_info: &AccountInfo<'info>,
+    system_program_info: &AccountInfo<'info>,
+    amount: u64,
+) -> ProgramResult {
+    check_writable(destination_info)?;
+    transfer_from_sol_vault(pool, sol_vault_info, sol_vault_bump, destination_info, system_program_info, amount)?;
+    if is_wrapped_sol_account(destination_info) {
+        invoke(
+            &create_sync_native_instruction(destination_info.key),
+            &[destination_info.clone(), token_program_info.clone()],
+        )?;
+    }
+    Ok(())
+}
+
+// Pool layouts deployed before the account type, version and reserved bytes
+// existed. Only read by MigratePool, which rewrites them in the current layout.
+#[derive(BorshDeserialize, Debug)]
+struct LegacyPool {
+    token_a: Pubkey,
+    token_b: Pubkey,
+    bump: u8,
+    reserve_a: u64,
+    reserve_b: u64,
+    total_lp_supply: u64,
+    fee_collected_a: u64,
+    fee_collected_b: u64,
+    fee_treasury: Pubkey,
+}
+
+impl LegacyPool {
+    const LEN: usize = 137;
+
+    // Current layout of the pool, with the settings it would have had if created today
+    fn migrate(self, config: &Config, now: i64) -> Pool {
+        Pool {
+            account_type: AccountType::Pool,
+            version: POOL_VERSION,
+            token_a: self.token_a,
+            token_b: self.token_b,
+            bump: self.bump,
+            reserve_a: self.reserve_a,
+            reserve_b: self.reserve_b,
+            total_lp_supply: self.total_lp_supply,
+            fee_collected_a: self.fee_collected_a,
+            fee_collected_b: self.fee_collected_b,
+            fee_treasury: self.fee_treasury,
+            authority: config.admin,
+            pending_authority: Pubkey::default(),
+            fee_bps: LEGACY_FEE_BPS,
+            protocol_fee_divisor: config.protocol_fee_divisor,
+            status: PoolStatus::Active,
+            price_a_cumulative: 0,
+            price_b_cumulative: 0,
+            last_update_timestamp: now,
+            reserved: [0; POOL_RESERVED_LEN],
+        }
+    }
+}
+
+#[derive(BorshDeserialize, Debug)]
+struct LegacyNativeSOLPool {
+    token_a: Pubkey,
+    token_b: Pubkey,
+    bump: u8,
+    reserve_a: u64,
+    reserve_b: u64,
+    total_lp_supply: u64,
+    fee_collected_sol: u64,
+    fee_collected_token: u64,
+    fee_treasury: Pubkey,
+    token_mint: Pubkey,
+}
+
+impl LegacyNativeSOLPool {
+    const LEN: usize = 169;
+
+    // Same as LegacyPool::migrate. The SOL itself moves to the SOL vault separately.
+    fn migrate(self, config: &Config, now: i64) -> NativeSOLPool {
+        NativeSOLPool {
+            account_type: AccountType::NativeSOLPool,
+            version: POOL_VERSION,
+            token_a: self.token_a,
+            token_b: self.token_b,
+            bump: self.bump,
+            reserve_a: self.reserve_a,
+            reserve_b: self.reserve_b,
+            total_lp_supply: self.total_lp_supply,
+            fee_collected_sol: self.fee_collected_sol,
+            fee_collected_token: self.fee_collected_token,
+            fee_treasury: self.fee_treasury,
+            token_mint: self.token_mint,
+            authority: config.admin,
+            pending_authority: Pubkey::default(),
+            fee_bps: LEGACY_FEE_BPS,
+            protocol_fee_divisor: config.protocol_fee_divisor,
+            status: PoolStatus::Active,
+            price_a_cumulative: 0,
+            price_b_cumulative: 0,
+            last_update_timestamp: now,
+            reserved: [0; POOL_RESERVED_LEN],
+        }
+    }
+}
+
+// Global program config, a singleton PDA at [b"config"] created by InitConfig
+#[derive(BorshSerialize, BorshDeserialize, Debug, ShankAccount)]
+pub struct Config {
+    pub account_type: AccountType,  // AccountType::Config
+    pub admin: Pubkey,
+    pub default_fee_bps: u16,       // Fee tier of pools created with InitPool
+    pub protocol_fee_divisor: u8,   // Protocol fee divisor of new pools
+    pub default_treasury: Pubkey,   // Fee treasury of new pools, Pubkey::default() for none
+    pub paused: bool,               // Global pause: blocks pool creation, swaps and deposits
+    pub bump: u8,
+}
+
+impl Sealed for Config {}
+
+impl IsInitialized for Config {
+    fn is_initialized(&self) -> bool {
+        self.admin != Pubkey::default()
+    }
+}
+
+impl Pack for Config {
+    const LEN: usize = 1 + 32 + 2 + 1 + 32 + 1 + 1; // 70 bytes
+    
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let config = Config::try_from_slice(src)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        config.account_type.check(AccountType::Config)?;
+        Ok(config)
+    }
+    
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let data = self.try_to_vec().unwrap();
+        dst[..data.len()].copy_from_slice(&data);
+    }
+}
+
+impl Config {
+    // Pool instructions take the config account last, after their own accounts.
+    // Returns the config and the remaining accounts.
+    fn split_from<'a, 'info>(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'info>],
+    ) -> Result<(Config, &'a [AccountInfo<'info>]), ProgramError> {
+        let (config_info, accounts) = accounts.split_last().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if *config_info.key != get_config_address(program_id).0 {
+            return Err(SwapError::InvalidConfig.into());
+        }
+        check_owner(config_info, program_id)?;
+        let config = Config::unpack(&config_info.data.borrow())?;
+        Ok((config, accounts))
+    }
+
+    fn check_not_paused(&self) -> ProgramResult {
+        if self.paused {
+            solana_program::log::sol_log("Program is paused");
+            return Err(SwapError::ProgramPaused.into());
+        }
+        Ok(())
+    }
+
+    fn check_admin(&self, admin_info: &AccountInfo) -> ProgramResult {
+        if *admin_info.key != self.admin {
+            solana_program::log::sol_log(&format!("Invalid admin: expected {}, got {}", self.admin, admin_info.key));
+            return Err(SwapError::InvalidAuthority.into());
+        }
+        Ok(())
+    }
+}
+
+fn get_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config"], program_id)
+}
+
+// Snapshot of a pool's cumulative prices. The TWAP between two observations is
+// (later.price_cumulative.wrapping_sub(earlier.price_cumulative)) / (later.timestamp - earlier.timestamp),
+// a Q64.64 price.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Observation {
+    pub timestamp: i64, // 0 for a slot that was never written
+    pub price_a_cumulative: u128,
+    pub price_b_cumulative: u128,
+}
+
+impl Observation {
+    const LEN: usize = 8 + 16 + 16; // 40 bytes
+}
+
+const OBSERVATION_CAPACITY: usize = 64;
+
+// Seconds between recorded observations, so the buffer always spans at least
+// OBSERVATION_CAPACITY * MIN_OBSERVATION_INTERVAL seconds of history and cannot be
+// flushed by a burst of RecordObservation calls
+const MIN_OBSERVATION_INTERVAL: i64 = 60;
+
+// Ring buffer of a pool's price observations, written by RecordObservation. The
+// struct only describes the account layout: it is too large for the SBF stack, so
+// handlers read and write the header and single observation slots in place.
+#[derive(BorshSerialize, BorshDeserialize, Debug, ShankAccount)]
+pub struct ObservationBuffer {
+    pub account_type: AccountType, // AccountType::ObservationBuffer
+    pub pool: Pubkey,
+    pub bump: u8,
+    pub index: u16,                        // Slot of the latest observation
+    pub observations: [Observation; 64],   // OBSERVATION_CAPACITY entries, oldest overwritten first
+}
+
+// Fields of an ObservationBuffer ahead of its observations
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+struct ObservationBufferHeader {
+    account_type: AccountType,
+    pool: Pubkey,
+    bump: u8,
+    index: u16,
+}
+
+impl ObservationBufferHeader {
+    const LEN: usize = 1 + 32 + 1 + 2; // 36 bytes
+    
+    fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() != ObservationBuffer::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let header = ObservationBufferHeader::try_from_slice(&data[..Self::LEN])
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        header.account_type.check(AccountType::ObservationBuffer)?;
+        if header.index as usize >= OBSERVATION_CAPACITY {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(header)
+    }
+    
+    fn pack(&self, data: &mut [u8]) {
+        let header = self.try_to_vec().unwrap();
+        data[..Self::LEN].copy_from_slice(&header);
+    }
+}
+
+impl ObservationBuffer {
+    const LEN: usize = ObservationBufferHeader::LEN + OBSERVATION_CAPACITY * Observation::LEN; // 2596 bytes
+    
+    fn observation_range(index: u16) -> std::ops::Range<usize> {
+        let start = ObservationBufferHeader::LEN + index as usize * Observation::LEN;
+        start..start + Observation::LEN
+    }
+    
+    fn read_observation(data: &[u8], index: u16) -> Result<Observation, ProgramError> {
+        Observation::try_from_slice(&data[Self::observation_range(index)])
+            .map_err(|_| ProgramError::InvalidAccountData)
+    }
+    
+    fn write_observation(data: &mut [u8], index: u16, observation: &Observation) {
+        let observation = observation.try_to_vec().unwrap();
+        data[Self::observation_range(index)].copy_from_slice(&observation);
+    }
+    
+    // Append an observation to the buffer in data, overwriting the oldest once it is
+    // full. Returns false without recording if the latest observation is less than
+    // MIN_OBSERVATION_INTERVAL seconds older.
+    fn record(header: &mut ObservationBufferHeader, data: &mut [u8], observation: Observation) -> Result<bool, ProgramError> {
+        let latest = Self::read_observation(data, header.index)?;
+        if observation.timestamp < latest.timestamp.saturating_add(MIN_OBSERVATION_INTERVAL) {
+            return Ok(false);
+        }
+        header.index = ((header.index as usize + 1) % OBSERVATION_CAPACITY) as u16;
+        Self::write_observation(data, header.index, &observation);
+        header.pack(data);
+        Ok(true)
+    }
+}
+
+fn get_observation_address(pool: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"observation", pool.as_ref()], program_id)
+}
+
+// Observation of a pool of either type, with its cumulative prices advanced to now
+// at the current reserves (the pool account itself is not written)
+fn observe_pool(pool_info: &AccountInfo) -> Result<Observation, ProgramError> {
+    let now = Clock::get()?.unix_timestamp;
+    let (price_a_cumulative, price_b_cumulative) = match AccountType::of(pool_info)? {
+        AccountType::Pool => Pool::unpack(&pool_info.data.borrow())?.price_cumulative_at(now),
+        AccountType::NativeSOLPool => NativeSOLPool::unpack(&pool_info.data.borrow())?.price_cumulative_at(now),
+        _ => return Err(SwapError::PoolTypeMismatch.into()),
+    };
+    Ok(Observation {
+        timestamp: now,
+        price_a_cumulative,
+        price_b_cumulative,
+    })
+}
+
+// Program instruction processor
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let instruction = TestProjectInstruction::try_from_slice(instruction_data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+        
+    match instruction {
+        TestProjectInstruction::InitPool { amount_a, amount_b } => {
+            process_init_pool(program_id, accounts, amount_a, amount_b, None)
+        }
+        TestProjectInstruction::AddLiquidity { amount_a, amount_b } => {
+            process_add_liquidity(program_id, accounts, amount_a, amount_b, 0)
+        }
+        TestProjectInstruction::RemoveLiquidity { lp_amount } => {
+            process_remove_liquidity(program_id, accounts, lp_amount, 0, 0)
+        }
+        TestProjectInstruction::Swap { amount_in, direction_a_to_b } => {
+            let swap_amount = SwapAmount::ExactIn { amount_in, minimum_amount_out: 0, max_price_impact_bps: None };
+            process_swap(program_id, accounts, swap_amount, direction_a_to_b)
+        }
+        TestProjectInstruction::MultihopSwap { amount_in, minimum_amount_out } => {
+            process_multihop_swap(program_id, accounts, amount_in, minimum_amount_out)
+        }
+        TestProjectInstruction::MultihopSwapWithPath { amount_in, minimum_amount_out, token_path } => {
+            process_multihop_swap_with_path(program_id, accounts, amount_in, minimum_amount_out, token_path)
+        }
+        TestProjectInstruction::CollectFees { pool } => {
+            process_collect_fees(program_id, accounts, pool)
+        }
+        TestProjectInstruction::WithdrawFees { pool, amount_a, amount_b } => {
+            process_withdraw_fees(program_id, accounts, pool, amount_a, amount_b)
+        }
+        TestProjectInstruction::SetFeeTreasury { pool, treasury } => {
+            process_set_fee_treasury(program_id, accounts, pool, treasury)
+        }
+        TestProjectInstruction::SwapWithSlippage { amount_in, minimum_amount_out, max_price_impact_bps, direction_a_to_b, valid_until } => {
+            check_deadline(valid_until)?;
+            let swap_amount = SwapAmount::ExactIn { amount_in, minimum_amount_out, max_price_impact_bps };
+            process_swap(program_id, accounts, swap_amount, direction_a_to_b)
+        }
+        TestProjectInstruction::SwapExactOut { amount_out, maximum_amount_in, direction_a_to_b, valid_until } => {
+            check_deadline(valid_until)?;
+            let swap_amount = SwapAmount::ExactOut { amount_out, maximum_amount_in };
+            process_swap(program_id, accounts, swap_amount, direction_a_to_b)
+        }
+        TestProjectInstruction::MultihopSwapExactOutWithPath { amount_out, maximum_amount_in, token_path, valid_until } => {
+            check_deadline(valid_until)?;
+            process_multihop_swap_exact_out_with_path(program_id, accounts, amount_out, maximum_amount_in, token_path)
+        }
+        TestProjectInstruction::AddLiquidityV2 { amount_a, amount_b, min_lp_out, valid_until } => {
+            check_deadline(valid_until)?;
+            process_add_liquidity(program_id, accounts, amount_a, amount_b, min_lp_out)
+        }
+        TestProjectInstruction::RemoveLiquidityV2 { lp_amount, min_amount_a, min_amount_b, valid_until } => {
+            check_deadline(valid_until)?;
+            process_remove_liquidity(program_id, accounts, lp_amount, min_amount_a, min_amount_b)
+        }
+        TestProjectInstruction::MultihopSwapV2 { amount_in, minimum_amount_out, valid_until } => {
+            check_deadline(valid_until)?;
+            process_multihop_swap(program_id, accounts, amount_in, minimum_amount_out)
+        }
+        TestProjectInstruction::MultihopSwapWithPathV2 { amount_in, minimum_amount_out, token_path, valid_until } => {
+            check_deadline(valid_until)?;
+            process_multihop_swap_with_path(program_id, accounts, amount_in, minimum_amount_out, token_path)
+        }
+        TestProjectInstruction::ProposePoolAuthority { pool, new_authority } => {
+            process_propose_pool_authority(program_id, accounts, pool, new_authority)
+        }
+        TestProjectInstruction::AcceptPoolAuthority { pool } => {
+            process_accept_pool_authority(program_id, accounts, pool)
+        }
+        TestProjectInstruction::InitPoolV2 { amount_a, amount_b, fee_bps } => {
+            process_init_pool(program_id, accounts, amount_a, amount_b, Some(fee_bps))
+        }
+        TestProjectInstruction::SetProtocolFee { pool, protocol_fee_divisor } => {
+            process_set_protocol_fee(program_id, accounts, pool, protocol_fee_divisor)
+        }
+        TestProjectInstruction::InitConfig { default_fee_bps, protocol_fee_divisor, default_treasury } => {
+            process_init_config(program_id, accounts, default_fee_bps, protocol_fee_divisor, default_treasury)
+        }
+        TestProjectInstruction::UpdateConfig { default_fee_bps, protocol_fee_divisor, default_treasury, paused, new_admin } => {
+            process_update_config(program_id, accounts, default_fee_bps, protocol_fee_divisor, default_treasury, paused, new_admin)
+        }
+        TestProjectInstruction::PausePool { pool } => {
+            process_set_pool_status(program_id, accounts, pool, PoolStatus::Paused)
+        }
+        TestProjectInstruction::UnpausePool { pool } => {
+            process_set_pool_status(program_id, accounts, pool, PoolStatus::Active)
+        }
+        TestProjectInstruction::Sync { pool } => {
+            process_sync(program_id, accounts, pool)
+        }
+        TestProjectInstruction::Skim { pool } => {
+            process_skim(program_id, accounts, pool)
+        }
+        TestProjectInstruction::MigratePool { pool } => {
+            process_migrate_pool(program_id, accounts, pool)
+        }
+        TestProjectInstruction::MultihopSwapRoute { amount_in, minimum_amount_out, route, destination, valid_until } => {
+            check_deadline(valid_until)?;
+            process_multihop_swap_route(program_id, accounts, amount_in, minimum_amount_out, route, destination)
+        }
+        TestProjectInstruction::SwapSplit { amount_in, minimum_amount_out, routes, valid_until } => {
+            check_deadline(valid_until)?;
+            process_swap_split(program_id, accounts, amount_in, minimum_amount_out, routes)
+        }
+        TestProjectInstruction::MultihopSwapCompact { amount_in, minimum_amount_out, token_path, valid_until } => {
+            check_deadline(valid_until)?;
+            process_multihop_swap_with_path(program_id, accounts, amount_in, minimum_amount_out, token_path)
+        }
+        TestProjectInstruction::InitObservations { pool } => {
+            process_init_observations(program_id, accounts, pool)
+        }
+        TestProjectInstruction::RecordObservation { pool } => {
+            process_record_observation(program_id, accounts, pool)
+        }
+    }
+}
+
+// Reject the instruction if the caller's deadline has passed
+fn check_deadline(valid_until: Option<Deadline>) -> ProgramResult {
+    let deadline = match valid_until {
+        Some(deadline) => deadline,
+        None => return Ok(()),
+    };
+
+    let clock = Clock::get()?;
+    if deadline.has_passed(&clock) {
+        solana_program::log::sol_log(&format!("Deadline exceeded: {:?} (slot {}, unix timestamp {})", deadline, clock.slot, clock.unix_timestamp));
+        return Err(SwapError::DeadlineExceeded.into());
+    }
+
+    Ok(())
+}
+
+fn process_init_pool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount_a: u64,
+    amount_b: u64,
+    fee_bps: Option<u16>,
+) -> ProgramResult {
+    let (config, accounts) = Config::split_from(program_id, accounts)?;
+    config.check_not_paused()?;
+    let fee_bps = fee_bps.unwrap_or(config.default_fee_bps);
+    check_fee_tier(fee_bps)?;
+
+    let init_accounts = InitPoolAccounts::load(accounts)?;
+    let InitPoolAccounts {
+        pool_info,
+        token_a_info,
+        token_b_info,
+        vault_a_info,
+        vault_b_info,
+        lp_mint_info,
+        user_info,
+        user_token_a_info,
+        user_token_b_info,
+        user_lp_info,
+        token_program_info,
+        system_program_info,
+        rent_info,
+        ..
+    } = init_accounts;
+
+    // Check if this is a native SOL pool initialization
+    let is_native_sol_pool = *token_a_info.key == NATIVE_SOL_MINT || *token_b_info.key == NATIVE_SOL_MINT;
+    
+    if is_native_sol_pool {
+        // Native SOL pool initialization logic
+        solana_program::log::sol_log("InitNativeSOLPool instruction called");
+        
+        // Determine which token is SOL and which is the SPL token
+        let (sol_amount, token_amount, token_mint) = if *token_a_info.key == NATIVE_SOL_MINT {
+            (amount_a, amount_b, *token_b_info.key)
+    } else {
+            (amount_b, amount_a, *token_a_info.key)
+        };
+
+        // Derive pool PDA for native SOL pool
+        let (pool_pda, _pool_bump) = Pubkey::find_program_address(
+            &[b"native_sol_pool", token_mint.as_ref()],
+            program_id,
+        );
+
+        // Verify pool account
+        if pool_info.key != &pool_pda {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
+
+        // Derive LP mint PDA
+        let (lp_mint_pda, _lp_mint_bump) = Pubkey::find_program_address(
+            &[b"native_sol_lp_mint", pool_pda.as_ref()],
+            program_id,
+        );
+
+        // Verify LP mint
+        if lp_mint_info.key != &lp_mint_pda {
+            return Err(SwapError::InvalidLpMint.into());
+        }
+
+        // Derive pool token vault PDA
+        let (pool_token_vault_pda, _pool_token_vault_bump) = Pubkey::find_program_address(
+            &[b"native_sol_vault", pool_pda.as_ref(), token_mint.as_ref()],
+            program_id,
+        );
+
+        // Verify pool token vault
+        if vault_b_info.key != &pool_token_vault_pda {
+            return Err(SwapError::InvalidVault.into());
+        }
+
+        // Verify SOL vault
+        let (sol_vault_pda, _sol_vault_bump) = get_sol_vault_address(&pool_pda, program_id);
+        if vault_a_info.key != &sol_vault_pda {
+            return Err(SwapError::InvalidVault.into());
+        }
+
+        // Check if pool account is already initialized
+        if !pool_info.data_is_empty() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        let rent_sysvar = Rent::from_account_info(rent_info)?;
+        let pool_space = NativeSOLPool::LEN;
+        let vault_space = 165; // Token account size
+        let mint_space = 82; // Mint account size
+        let pool_lamports = rent_sysvar.minimum_balance(pool_space);
+        let vault_lamports = rent_sysvar.minimum_balance(vault_space);
+        let mint_lamports = rent_sysvar.minimum_balance(mint_space);
+        let sol_vault_lamports = rent_sysvar.minimum_balance(0);
+
+        // Create pool account
+        let pool_signer_seeds: &[&[_]] = &[
+            b"native_sol_pool",
+            token_mint.as_ref(),
+            &[_pool_bump],
+        ];
+
+        invoke_signed(
+            &system_instruction::create_account(
+                user_info.key,
+                pool_info.key,
+                pool_lamports,
+                pool_space as u64,
+                program_id,
+            ),
+            &[
+                user_info.clone(),
+                pool_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[pool_signer_seeds],
+        )?;
+
+        // Create pool token vault
+        let vault_signer_seeds: &[&[_]] = &[
+            b"native_sol_vault",
+            pool_pda.as_ref(),
+            token_mint.as_ref(),
+            &[_pool_token_vault_bump],
+        ];
+
+        invoke_signed(
+            &system_instruction::create_account(
+                user_info.key,
+                vault_b_info.key,
+                vault_lamports,
+                vault_space as u64,
+                &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
+            ),
+            &[
+                user_info.clone(),
+                vault_b_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[vault_signer_seeds],
+        )?;
+
+        // Initialize pool token vault as token account
+        invoke_signed(
+            &create_initialize_account_instruction(
+                vault_b_info.key,
+                &token_mint,
+                vault_b_info.key, // Vault is its own authority
+            ),
+            &[
+                vault_b_info.clone(),
+                token_b_info.clone(),
+                vault_b_info.clone(),
+                rent_info.clone(),
+            ],
+            &[vault_signer_seeds],
+        )?;
+
+        // Create LP mint account
+        let lp_mint_signer_seeds: &[&[_]] = &[
+            b"native_sol_lp_mint",
+            pool_pda.as_ref(),
+            &[_lp_mint_bump],
+        ];
+
+        invoke_signed(
+            &system_instruction::create_account(
+                user_info.key,
+                lp_mint_info.key,
+                mint_lamports,
+                mint_space as u64,
+                &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
+            ),
+            &[
+                user_info.clone(),
+                lp_mint_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[lp_mint_signer_seeds],
+        )?;
+
+        // Initialize LP mint
+        invoke_signed(
+            &create_initialize_mint_instruction(
+                lp_mint_info.key,
+                0, // decimals (0 for LP token)
+                pool_info.key, // mint authority is pool
+                None,          // no freeze authority
+            ),
+            &[
+                lp_mint_info.clone(),
+                rent_info.clone(),
+            ],
+            &[lp_mint_signer_seeds],
+        )?;
+
+        // Fund the SOL vault with its rent-exempt minimum plus the SOL reserve. A transfer
+        // to an empty address creates it as a system account, so no signature is needed.
+        invoke(
+            &system_instruction::transfer(
+                user_info.key,
+                vault_a_info.key,
+                math::add(sol_vault_lamports, sol_amount)?,
+            ),
+            &[
+                user_info.clone(),
+                vault_a_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+
+        // Transfer tokens to pool vault
+        transfer_to_vault(user_token_b_info, vault_b_info, user_info, token_program_info, token_amount)?;
+
+        // Create user LP ATA if it doesn't exist
+        invoke(
+            &create_associated_token_account_instruction(
+                user_info.key,
+                user_lp_info.key,
+                user_info.key,
+                lp_mint_info.key,
+            ),
+            &[
+                user_info.clone(),
+                user_lp_info.clone(),
+                user_info.clone(),
+                lp_mint_info.clone(),
+                rent_info.clone(),
+                token_program_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+
+        // Calculate initial liquidity (geometric mean)
+        let liquidity: u64 = math::sqrt_product(sol_amount, token_amount)?;
+        check_initial_liquidity(liquidity)?;
+        lock_minimum_liquidity(program_id, &init_accounts, pool_signer_seeds)?;
+
+        // Mint LP tokens to user, minus the locked minimum liquidity
+        invoke_signed(
+            &create_mint_to_instruction(
+                lp_mint_info.key,
+                user_lp_info.key,
+                pool_info.key,
+                math::sub(liquidity, MINIMUM_LIQUIDITY)?,
+            ),
+            &[
+                lp_mint_info.clone(),
+                user_lp_info.clone(),
+                pool_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[pool_signer_seeds],
+        )?;
+
+        // Initialize pool state
+        let pool = NativeSOLPool {
+            account_type: AccountType::NativeSOLPool,
+            version: POOL_VERSION,
+            token_a: NATIVE_SOL_MINT,
+            token_b: token_mint,
+            bump: _pool_bump,
+            reserve_a: sol_amount,
+            reserve_b: token_amount,
+            total_lp_supply: liquidity,
+            fee_collected_sol: 0,
+            fee_collected_token: 0,
+            fee_treasury: config.default_treasury, // Can be changed via SetFeeTreasury
+            token_mint,
+            authority: config.admin,
+            pending_authority: Pubkey::default(),
+            fee_bps,
+            protocol_fee_divisor: config.protocol_fee_divisor,
+            status: PoolStatus::Active,
+            price_a_cumulative: 0,
+            price_b_cumulative: 0,
+            last_update_timestamp: Clock::get()?.unix_timestamp,
+            reserved: [0; POOL_RESERVED_LEN],
+        };
+
+        let mut pool_data = pool_info.try_borrow_mut_data()?;
+        if pool_data.len() < NativeSOLPool::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        pool.pack_into_slice(&mut pool_data[..NativeSOLPool::LEN]);
+
+        solana_program::log::sol_log(&format!("Pool initialized with {} SOL and {} tokens, {} LP tokens minted", sol_amount, token_amount, liquidity));
+
+        return Ok(());
+    }
+
+    // Derive pool address and bump
+    let (pool_pubkey, _pool_bump) = Pubkey::find_program_address(
+        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
+        program_id,
+    );
+    if pool_pubkey != *pool_info.key {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
+
+    // Derive vault addresses and verify them
+    let (vault_a_pubkey, vault_a_bump) = get_vault_address(&pool_pubkey, token_a_info.key, program_id);
+    let (vault_b_pubkey, vault_b_bump) = get_vault_address(&pool_pubkey, token_b_info.key, program_id);
+    
+    if vault_a_pubkey != *vault_a_info.key {
+        return Err(SwapError::InvalidVault.into());
+    }
+    if vault_b_pubkey != *vault_b_info.key {
+        return Err(SwapError::InvalidVault.into());
+    }
+
+    let rent = Rent::from_account_info(rent_info)?;
+    let pool_space = Pool::LEN;
+    let vault_space = 165; // Token account size
+    let pool_lamports = rent.minimum_balance(pool_space);
+    let vault_lamports = rent.minimum_balance(vault_space);
+
+    // Create pool account
+    let pool_signer_seeds: &[&[_]] = &[
+            b"pool",
+            token_a_info.key.as_ref(),
+            token_b_info.key.as_ref(),
+            &[_pool_bump],
+    ];
+
+    invoke_signed(
+        &system_instruction::create_account(
+            user_info.key,
+            pool_info.key,
+            pool_lamports,
+            pool_space as u64,
+            program_id,
+        ),
+        &[
+            user_info.clone(),
+            pool_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[pool_signer_seeds],
+    )?;
+
+    // Create vault A as PDA
+    let vault_a_signer_seeds: &[&[_]] = &[
+            b"vault",
+            pool_info.key.as_ref(),
+            token_a_info.key.as_ref(),
+            &[vault_a_bump],
+    ];
+
+    invoke_signed(
+        &system_instruction::create_account(
+            user_info.key,
+            vault_a_info.key,
+            vault_lamports,
+            vault_space as u64,
+            &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
+        ),
+        &[
+            user_info.clone(),
+            vault_a_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[vault_a_signer_seeds],
+    )?;
+
+    // Create vault B as PDA
+        let vault_b_signer_seeds: &[&[_]] = &[
+            b"vault",
+            pool_info.key.as_ref(),
+            token_b_info.key.as_ref(),
+            &[vault_b_bump],
+        ];
+
+        invoke_signed(
             &system_instruction::create_account(
                 user_info.key,
                 vault_b_info.key,
@@ -669,1862 +2433,3401 @@ fn process_init_pool(
                 &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
             ),
             &[
-                user_info.clone(),
-                vault_b_info.clone(),
-                system_program_info.clone(),
+                user_info.clone(),
+                vault_b_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[vault_b_signer_seeds],
+        )?;
+
+    // Initialize vault A as token account
+    invoke_signed(
+        &create_initialize_account_instruction(
+            vault_a_info.key,
+            token_a_info.key,
+            vault_a_info.key, // Vault is its own authority
+        ),
+        &[
+            vault_a_info.clone(),
+            token_a_info.clone(),
+            vault_a_info.clone(),
+            rent_info.clone(),
+        ],
+        &[vault_a_signer_seeds],
+    )?;
+
+    // Initialize vault B as token account
+        invoke_signed(
+            &create_initialize_account_instruction(
+                vault_b_info.key,
+                token_b_info.key,
+                vault_b_info.key, // Vault is its own authority
+            ),
+            &[
+                vault_b_info.clone(),
+                token_b_info.clone(),
+                vault_b_info.clone(),
+                rent_info.clone(),
+            ],
+            &[vault_b_signer_seeds],
+        )?;
+
+    // Create LP mint account
+    let mint_space = 82; // Mint account size
+    let mint_lamports = rent.minimum_balance(mint_space);
+    let (lp_mint_pubkey, _lp_mint_bump) = Pubkey::find_program_address(
+            &[b"mint", pool_info.key.as_ref()],
+            program_id,
+    );
+    if lp_mint_pubkey != *lp_mint_info.key {
+        return Err(SwapError::InvalidLpMint.into());
+    }
+    let lp_mint_signer_seeds: &[&[_]] = &[
+            b"mint",
+            pool_info.key.as_ref(),
+            &[_lp_mint_bump],
+    ];
+
+    invoke_signed(
+        &system_instruction::create_account(
+            user_info.key,
+            lp_mint_info.key,
+            mint_lamports,
+            mint_space as u64,
+            &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
+        ),
+        &[
+            user_info.clone(),
+            lp_mint_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[lp_mint_signer_seeds],
+    )?;
+
+    // Initialize LP mint as token mint
+    invoke_signed(
+        &create_initialize_mint_instruction(
+            lp_mint_info.key,
+            0, // decimals (0 for LP token)
+            pool_info.key, // mint authority is pool
+            None,          // no freeze authority
+        ),
+        &[
+            lp_mint_info.clone(),
+            rent_info.clone(),
+        ],
+        &[lp_mint_signer_seeds],
+    )?;
+
+    // Transfer tokens to vaults
+    transfer_to_vault(user_token_a_info, vault_a_info, user_info, token_program_info, amount_a)?;
+
+    transfer_to_vault(user_token_b_info, vault_b_info, user_info, token_program_info, amount_b)?;
+
+    // Create user LP ATA if it doesn't exist
+    invoke(
+        &create_associated_token_account_instruction(
+            user_info.key,
+            user_lp_info.key,
+            user_info.key,
+            lp_mint_info.key,
+        ),
+        &[
+            user_info.clone(),
+            user_lp_info.clone(),
+            user_info.clone(),
+            lp_mint_info.clone(),
+            rent_info.clone(),
+            token_program_info.clone(),
+            system_program_info.clone(),
+        ],
+    )?;
+
+    // Calculate liquidity
+    let liquidity: u64 = math::sqrt_product(amount_a, amount_b)?;
+    check_initial_liquidity(liquidity)?;
+    lock_minimum_liquidity(program_id, &init_accounts, pool_signer_seeds)?;
+
+    // Mint LP tokens, minus the locked minimum liquidity
+    invoke_signed(
+        &create_mint_to_instruction(
+            lp_mint_info.key,
+            user_lp_info.key,
+            pool_info.key,
+            math::sub(liquidity, MINIMUM_LIQUIDITY)?,
+        ),
+        &[
+            lp_mint_info.clone(),
+            user_lp_info.clone(),
+            pool_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[pool_signer_seeds],
+    )?;
+
+    // Initialize pool state
+        let pool = Pool {
+            account_type: AccountType::Pool,
+            version: POOL_VERSION,
+            token_a: *token_a_info.key,
+            token_b: *token_b_info.key,
+            bump: _pool_bump,
+            reserve_a: amount_a,
+            reserve_b: amount_b,
+            total_lp_supply: liquidity,
+            fee_collected_a: 0,
+            fee_collected_b: 0,
+            fee_treasury: config.default_treasury, // Can be changed via SetFeeTreasury
+            authority: config.admin,
+            pending_authority: Pubkey::default(),
+            fee_bps,
+            protocol_fee_divisor: config.protocol_fee_divisor,
+            status: PoolStatus::Active,
+            price_a_cumulative: 0,
+            price_b_cumulative: 0,
+            last_update_timestamp: Clock::get()?.unix_timestamp,
+            reserved: [0; POOL_RESERVED_LEN],
+        };
+        Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+// LP tokens locked forever at pool creation, so the pool can never be drained back
+// to zero supply and the first depositor cannot inflate the LP share price
+const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+fn check_initial_liquidity(liquidity: u64) -> ProgramResult {
+    if liquidity <= MINIMUM_LIQUIDITY {
+        solana_program::log::sol_log(&format!("Initial liquidity {} must exceed {}", liquidity, MINIMUM_LIQUIDITY));
+        return Err(SwapError::InitialLiquidityTooLow.into());
+    }
+    Ok(())
+}
+
+// Mint MINIMUM_LIQUIDITY to the locked LP account, a token account at the
+// [b"locked_lp", pool] PDA that owns itself. No instruction ever signs for it,
+// so these LP tokens can never be moved or burned.
+fn lock_minimum_liquidity(
+    program_id: &Pubkey,
+    ctx: &InitPoolAccounts,
+    pool_signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    let (locked_lp_pubkey, locked_lp_bump) = Pubkey::find_program_address(
+        &[b"locked_lp", ctx.pool_info.key.as_ref()],
+        program_id,
+    );
+    if *ctx.locked_lp_info.key != locked_lp_pubkey {
+        return Err(SwapError::InvalidLockedLpAccount.into());
+    }
+    
+    let locked_lp_signer_seeds: &[&[_]] = &[
+        b"locked_lp",
+        ctx.pool_info.key.as_ref(),
+        &[locked_lp_bump],
+    ];
+    
+    let rent_sysvar = Rent::from_account_info(ctx.rent_info)?;
+    let token_account_space = 165; // Token account size
+    invoke_signed(
+        &system_instruction::create_account(
+            ctx.user_info.key,
+            ctx.locked_lp_info.key,
+            rent_sysvar.minimum_balance(token_account_space),
+            token_account_space as u64,
+            &gorbchain_token_program_id(),
+        ),
+        &[
+            ctx.user_info.clone(),
+            ctx.locked_lp_info.clone(),
+            ctx.system_program_info.clone(),
+        ],
+        &[locked_lp_signer_seeds],
+    )?;
+    
+    invoke(
+        &create_initialize_account_instruction(
+            ctx.locked_lp_info.key,
+            ctx.lp_mint_info.key,
+            ctx.locked_lp_info.key,
+        ),
+        &[
+            ctx.locked_lp_info.clone(),
+            ctx.lp_mint_info.clone(),
+            ctx.locked_lp_info.clone(),
+            ctx.rent_info.clone(),
+        ],
+    )?;
+    
+    invoke_signed(
+        &create_mint_to_instruction(
+            ctx.lp_mint_info.key,
+            ctx.locked_lp_info.key,
+            ctx.pool_info.key,
+            MINIMUM_LIQUIDITY,
+        ),
+        &[
+            ctx.lp_mint_info.clone(),
+            ctx.locked_lp_info.clone(),
+            ctx.pool_info.clone(),
+            ctx.token_program_info.clone(),
+        ],
+        &[pool_signer_seeds],
+    )?;
+    
+    solana_program::log::sol_log(&format!("Locked {} LP tokens in {}", MINIMUM_LIQUIDITY, locked_lp_pubkey));
+    
+    Ok(())
+}
+
+fn process_add_liquidity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount_a: u64,
+    amount_b: u64,
+    min_lp_out: u64,
+) -> ProgramResult {
+    let (config, accounts) = Config::split_from(program_id, accounts)?;
+    config.check_not_paused()?;
+    
+    let AddLiquidityAccounts {
+        pool_info,
+        token_a_info,
+        token_b_info,
+        vault_a_info,
+        vault_b_info,
+        lp_mint_info,
+        user_token_a_info,
+        user_token_b_info,
+        user_lp_info,
+        user_info,
+        token_program_info,
+        system_program_info,
+    } = AddLiquidityAccounts::load(program_id, accounts)?;
+
+    // Check if this is a native SOL pool add liquidity
+    let is_native_sol_pool = *token_a_info.key == NATIVE_SOL_MINT || *token_b_info.key == NATIVE_SOL_MINT;
+    
+    if is_native_sol_pool {
+        // Native SOL pool add liquidity logic
+        solana_program::log::sol_log("AddLiquidityNativeSOL instruction called");
+        
+        // Determine which token is SOL and which is the SPL token
+        let (sol_amount, token_amount, token_mint) = if *token_a_info.key == NATIVE_SOL_MINT {
+            (amount_a, amount_b, *token_b_info.key)
+    } else {
+            (amount_b, amount_a, *token_a_info.key)
+        };
+
+        solana_program::log::sol_log(&format!("SOL amount: {}", sol_amount));
+        solana_program::log::sol_log(&format!("Token amount: {}", token_amount));
+
+        // Load pool state
+        let mut pool = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+        pool.update_price_cumulative()?;
+        pool.check_active()?;
+        
+        // Verify pool seeds
+        let (pool_pda, _pool_bump) = Pubkey::find_program_address(
+            &[b"native_sol_pool", token_mint.as_ref()],
+            program_id,
+        );
+        if pool_info.key != &pool_pda {
+            return Err(SwapError::InvalidPoolAddress.into());
+        }
+
+        // Verify pool token vault
+        let (pool_token_vault_pda, _pool_token_vault_bump) = Pubkey::find_program_address(
+            &[b"native_sol_vault", pool_pda.as_ref(), token_mint.as_ref()],
+            program_id,
+        );
+        if vault_b_info.key != &pool_token_vault_pda {
+            return Err(SwapError::InvalidVault.into());
+        }
+        // Verify SOL vault
+        let (sol_vault_pda, _sol_vault_bump) = get_sol_vault_address(&pool_pda, program_id);
+        if vault_a_info.key != &sol_vault_pda {
+            return Err(SwapError::InvalidVault.into());
+        }
+        pool.reconcile(vault_a_info, vault_b_info)?;
+
+        // Verify LP mint
+        let (lp_mint_pda, _lp_mint_bump) = Pubkey::find_program_address(
+            &[b"native_sol_lp_mint", pool_pda.as_ref()],
+            program_id,
+        );
+        if lp_mint_info.key != &lp_mint_pda {
+            return Err(SwapError::InvalidLpMint.into());
+        }
+
+        // Calculate final amounts maintaining ratio
+        let (final_amount_sol, final_amount_token) = if pool.reserve_a > 0 && pool.reserve_b > 0 {
+            let required_token = math::mul_div(sol_amount, pool.reserve_b, pool.reserve_a)?;
+            if required_token <= token_amount {
+                (sol_amount, required_token)
+            } else {
+                let required_sol = math::mul_div(token_amount, pool.reserve_a, pool.reserve_b)?;
+                (required_sol, token_amount)
+            }
+        } else {
+            (sol_amount, token_amount)
+        };
+
+        // Transfer SOL to the SOL vault, unwrapping it first if paid from a wSOL account
+        deposit_sol(
+            user_token_a_info,
+            user_info,
+            vault_a_info,
+            token_program_info,
+            system_program_info.ok_or(ProgramError::NotEnoughAccountKeys)?,
+            final_amount_sol,
+        )?;
+
+        // Transfer tokens from user to pool vault
+        transfer_to_vault(user_token_b_info, vault_b_info, user_info, token_program_info, final_amount_token)?;
+
+        // Calculate liquidity to mint
+        let liquidity = if pool.total_lp_supply == 0 {
+            math::sqrt_product(final_amount_sol, final_amount_token)?
+        } else {
+            math::mul_div(final_amount_sol, pool.total_lp_supply, pool.reserve_a)?
+        };
+        check_liquidity_minted(liquidity, min_lp_out)?;
+
+        // Mint LP tokens
+        let pool_signer_seeds: &[&[_]] = &[
+            b"native_sol_pool",
+            token_mint.as_ref(),
+            &[_pool_bump],
+        ];
+        
+        invoke_signed(
+            &create_mint_to_instruction(
+                lp_mint_info.key,
+                user_lp_info.key,
+                pool_info.key,
+                liquidity,
+            ),
+            &[
+                lp_mint_info.clone(),
+                user_lp_info.clone(),
+                pool_info.clone(),
+                token_program_info.clone(),
             ],
-            &[vault_b_signer_seeds],
+            &[pool_signer_seeds],
         )?;
 
-    // Initialize vault A as token account
+        // Update pool state
+        pool.reserve_a = math::add(pool.reserve_a, final_amount_sol)?;
+        pool.reserve_b = math::add(pool.reserve_b, final_amount_token)?;
+        pool.total_lp_supply = math::add(pool.total_lp_supply, liquidity)?;
+        pool.check_balances(vault_a_info, vault_b_info)?;
+        
+        // Pack updated pool state
+        let mut pool_data = pool_info.try_borrow_mut_data()?;
+        pool.pack_into_slice(&mut pool_data[..NativeSOLPool::LEN]);
+
+        solana_program::log::sol_log(&format!("Liquidity added: {} SOL, {} tokens, {} LP tokens minted", final_amount_sol, final_amount_token, liquidity));
+        
+        return Ok(());
+    }
+
+    let mut pool = Pool::unpack(&pool_info.data.borrow())?;
+    pool.update_price_cumulative()?;
+    pool.check_active()?;
+    
+    // Store token addresses before pool is moved
+    let token_a = pool.token_a;
+    let token_b = pool.token_b;
+    
+    // Verify pool seeds
+    let (pool_pubkey, _) = Pubkey::find_program_address(
+        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
+        program_id,
+    );
+    if pool_pubkey != *pool_info.key {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
+
+    // Verify vault addresses
+    let (vault_a_pubkey, _) = get_vault_address(pool_info.key, &token_a, program_id);
+    let (vault_b_pubkey, _) = get_vault_address(pool_info.key, &token_b, program_id);
+    
+    if vault_a_pubkey != *vault_a_info.key || vault_b_pubkey != *vault_b_info.key {
+        return Err(SwapError::InvalidVault.into());
+    }
+    pool.reconcile(vault_a_info, vault_b_info)?;
+
+    let reserve_a = pool.reserve_a;
+    let reserve_b = pool.reserve_b;
+    let supply = pool.total_lp_supply;
+
+    // Calculate final amounts maintaining ratio
+    let (final_amount_a, final_amount_b) = if reserve_a > 0 && reserve_b > 0 {
+        let required_b = math::mul_div(amount_a, reserve_b, reserve_a)?;
+        if required_b <= amount_b {
+            (amount_a, required_b)
+        } else {
+            let required_a = math::mul_div(amount_b, reserve_a, reserve_b)?;
+            (required_a, amount_b)
+        }
+    } else {
+        (amount_a, amount_b)
+    };
+
+    // Transfer tokens to vaults
+    transfer_to_vault(user_token_a_info, vault_a_info, user_info, token_program_info, final_amount_a)?;
+
+    transfer_to_vault(user_token_b_info, vault_b_info, user_info, token_program_info, final_amount_b)?;
+
+    // Calculate liquidity to mint
+    let liquidity = if supply == 0 {
+        math::sqrt_product(final_amount_a, final_amount_b)?
+    } else {
+        math::mul_div(final_amount_a, supply, reserve_a)?
+    };
+    check_liquidity_minted(liquidity, min_lp_out)?;
+
+    // Mint LP tokens
+    let pool_signer_seeds: &[&[_]] = &[
+        b"pool",
+        pool.token_a.as_ref(),
+        pool.token_b.as_ref(),
+        &[pool.bump],
+    ];
+    
     invoke_signed(
-        &create_initialize_account_instruction(
-            vault_a_info.key,
-            token_a_info.key,
-            vault_a_info.key, // Vault is its own authority
+        &create_mint_to_instruction(
+            lp_mint_info.key,
+            user_lp_info.key,
+            pool_info.key,
+            liquidity,
         ),
         &[
-            vault_a_info.clone(),
-            token_a_info.clone(),
-            vault_a_info.clone(),
-            rent_info.clone(),
+            lp_mint_info.clone(),
+            user_lp_info.clone(),
+            pool_info.clone(),
+            token_program_info.clone(),
         ],
-        &[vault_a_signer_seeds],
+        &[pool_signer_seeds],
     )?;
 
-    // Initialize vault B as token account
-        invoke_signed(
-            &create_initialize_account_instruction(
-                vault_b_info.key,
-                token_b_info.key,
-                vault_b_info.key, // Vault is its own authority
-            ),
-            &[
-                vault_b_info.clone(),
-                token_b_info.clone(),
-                vault_b_info.clone(),
-                rent_info.clone(),
-            ],
-            &[vault_b_signer_seeds],
-        )?;
+    // Update pool state
+    pool.reserve_a = math::add(pool.reserve_a, final_amount_a)?;
+    pool.reserve_b = math::add(pool.reserve_b, final_amount_b)?;
+    pool.total_lp_supply = math::add(pool.total_lp_supply, liquidity)?;
+    pool.check_vault_balances(vault_a_info, vault_b_info)?;
+    
+    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
 
-    // Create LP mint account
-    let mint_space = 82; // Mint account size
-    let mint_lamports = rent.minimum_balance(mint_space);
-    let (lp_mint_pubkey, _lp_mint_bump) = Pubkey::find_program_address(
-            &[b"mint", pool_info.key.as_ref()],
-            program_id,
+    Ok(())
+}
+
+fn process_remove_liquidity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lp_amount: u64,
+    min_amount_a: u64,
+    min_amount_b: u64,
+) -> ProgramResult {
+    let RemoveLiquidityAccounts {
+        pool_info,
+        token_a_info,
+        token_b_info,
+        vault_a_info,
+        vault_b_info,
+        lp_mint_info,
+        user_lp_info,
+        user_token_a_info,
+        user_token_b_info,
+        user_info,
+        token_program_info,
+        system_program_info,
+    } = RemoveLiquidityAccounts::load(program_id, accounts)?;
+
+    // Check if this is a native SOL pool remove liquidity
+    let is_native_sol_pool = *token_a_info.key == NATIVE_SOL_MINT || *token_b_info.key == NATIVE_SOL_MINT;
+    
+    if is_native_sol_pool {
+        // Native SOL pool remove liquidity logic
+        solana_program::log::sol_log("RemoveLiquidityNativeSOL instruction called");
+        solana_program::log::sol_log(&format!("LP amount: {}", lp_amount));
+        
+        // Determine which token is SOL and which is the SPL token
+        let token_mint = if *token_a_info.key == NATIVE_SOL_MINT {
+            *token_b_info.key
+        } else {
+            *token_a_info.key
+        };
+
+        // Load pool state
+    let mut pool = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+    pool.update_price_cumulative()?;
+    
+    // Verify pool seeds
+        let (pool_pda, _pool_bump) = Pubkey::find_program_address(
+            &[b"native_sol_pool", token_mint.as_ref()],
+        program_id,
     );
-    if lp_mint_pubkey != *lp_mint_info.key {
-        return Err(ProgramError::InvalidSeeds);
+        if pool_info.key != &pool_pda {
+        return Err(SwapError::InvalidPoolAddress.into());
     }
-    let lp_mint_signer_seeds: &[&[_]] = &[
-            b"mint",
-            pool_info.key.as_ref(),
-            &[_lp_mint_bump],
-    ];
 
-    invoke_signed(
-        &system_instruction::create_account(
+    // Verify pool token vault
+    let (pool_token_vault_pda, _pool_token_vault_bump) = Pubkey::find_program_address(
+            &[b"native_sol_vault", pool_pda.as_ref(), token_mint.as_ref()],
+        program_id,
+    );
+        if vault_b_info.key != &pool_token_vault_pda {
+        return Err(SwapError::InvalidVault.into());
+    }
+    // Verify SOL vault
+    let (sol_vault_pda, sol_vault_bump) = get_sol_vault_address(&pool_pda, program_id);
+    if vault_a_info.key != &sol_vault_pda {
+        return Err(SwapError::InvalidVault.into());
+    }
+    pool.reconcile(vault_a_info, vault_b_info)?;
+
+    // Verify LP mint
+    let (lp_mint_pda, _lp_mint_bump) = Pubkey::find_program_address(
+            &[b"native_sol_lp_mint", pool_pda.as_ref()],
+        program_id,
+    );
+    if lp_mint_info.key != &lp_mint_pda {
+        return Err(SwapError::InvalidLpMint.into());
+    }
+
+        let sol_reserve = pool.reserve_a;
+        let token_reserve = pool.reserve_b;
+        let supply = pool.total_lp_supply;
+
+        if lp_amount == 0 {
+            return Err(SwapError::ZeroAmount.into());
+        }
+        if supply == 0 {
+            return Err(SwapError::ZeroLiquidity.into());
+        }
+
+        // Calculate amounts to withdraw
+        let amount_sol = math::mul_div(lp_amount, sol_reserve, supply)?;
+        let amount_token = math::mul_div(lp_amount, token_reserve, supply)?;
+
+        // Minimums follow the token_a / token_b account order, like the amounts of AddLiquidity
+        let (min_amount_sol, min_amount_token) = if *token_a_info.key == NATIVE_SOL_MINT {
+            (min_amount_a, min_amount_b)
+        } else {
+            (min_amount_b, min_amount_a)
+        };
+        check_withdrawal_amounts(amount_sol, amount_token, min_amount_sol, min_amount_token)?;
+
+        // Burn LP tokens
+    invoke(
+            &create_burn_instruction(
+                user_lp_info.key,
+                lp_mint_info.key,
             user_info.key,
-            lp_mint_info.key,
-            mint_lamports,
-            mint_space as u64,
-            &Pubkey::from_str(GORBCHAIN_SPL_TOKEN_PROGRAM).unwrap(),
+                lp_amount,
         ),
         &[
+                user_lp_info.clone(),
+                lp_mint_info.clone(),
             user_info.clone(),
-            lp_mint_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[lp_mint_signer_seeds],
-    )?;
-
-    // Initialize LP mint as token mint
-    invoke_signed(
-        &create_initialize_mint_instruction(
-            lp_mint_info.key,
-            0, // decimals (0 for LP token)
-            pool_info.key, // mint authority is pool
-            None,          // no freeze authority
-        ),
-        &[
-            lp_mint_info.clone(),
-            rent_info.clone(),
+            token_program_info.clone(),
         ],
-        &[lp_mint_signer_seeds],
     )?;
 
-    // Transfer tokens to vaults
-        invoke(
-            &create_transfer_instruction(
-                user_token_a_info.key,
-                vault_a_info.key,
-                user_info.key,
-                amount_a,
-            ),
-            &[
-                user_token_a_info.clone(),
-                vault_a_info.clone(),
-                user_info.clone(),
-                token_program_info.clone(),
-            ],
+        // Transfer SOL from the SOL vault to user, as wSOL if a wSOL account was passed
+        withdraw_sol(
+            &pool_pda,
+            vault_a_info,
+            sol_vault_bump,
+            sol_destination(user_token_a_info, user_info),
+            token_program_info,
+            system_program_info.ok_or(ProgramError::NotEnoughAccountKeys)?,
+            amount_sol,
         )?;
 
-        invoke(
+        // Transfer tokens from pool vault to user using vault PDA as authority
+        let vault_signer_seeds: &[&[_]] = &[
+            b"native_sol_vault",
+            pool_pda.as_ref(),
+            token_mint.as_ref(),
+            &[_pool_token_vault_bump],
+        ];
+        
+        invoke_signed(
             &create_transfer_instruction(
+                vault_b_info.key,
                 user_token_b_info.key,
                 vault_b_info.key,
-                user_info.key,
-                amount_b,
+                amount_token,
             ),
             &[
+                vault_b_info.clone(),
                 user_token_b_info.clone(),
                 vault_b_info.clone(),
-                user_info.clone(),
                 token_program_info.clone(),
             ],
+            &[vault_signer_seeds],
         )?;
 
-    // Create user LP ATA if it doesn't exist
+        // Update pool state
+        pool.reserve_a = math::sub(pool.reserve_a, amount_sol)?;
+        pool.reserve_b = math::sub(pool.reserve_b, amount_token)?;
+        pool.total_lp_supply = math::sub(pool.total_lp_supply, lp_amount)?;
+        pool.check_balances(vault_a_info, vault_b_info)?;
+        
+        // Pack updated pool state
+        let mut pool_data = pool_info.try_borrow_mut_data()?;
+        pool.pack_into_slice(&mut pool_data[..NativeSOLPool::LEN]);
+
+        solana_program::log::sol_log(&format!("Liquidity removed: {} LP tokens -> {} SOL, {} tokens", lp_amount, amount_sol, amount_token));
+        
+        return Ok(());
+    }
+
+    let mut pool = Pool::unpack(&pool_info.data.borrow())?;
+    pool.update_price_cumulative()?;
+    
+    // Verify pool seeds
+    let (pool_pubkey, _) = Pubkey::find_program_address(
+        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
+        program_id,
+    );
+    if pool_pubkey != *pool_info.key {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
+
+    // Verify vault addresses and get bumps
+    let (vault_a_pubkey, vault_a_bump) = get_vault_address(pool_info.key, &pool.token_a, program_id);
+    let (vault_b_pubkey, vault_b_bump) = get_vault_address(pool_info.key, &pool.token_b, program_id);
+    
+    if vault_a_pubkey != *vault_a_info.key || vault_b_pubkey != *vault_b_info.key {
+        return Err(SwapError::InvalidVault.into());
+    }
+    pool.reconcile(vault_a_info, vault_b_info)?;
+
+    let reserve_a = pool.reserve_a;
+    let reserve_b = pool.reserve_b;
+    let supply = pool.total_lp_supply;
+
+    if lp_amount == 0 {
+        return Err(SwapError::ZeroAmount.into());
+    }
+    if supply == 0 {
+        return Err(SwapError::ZeroLiquidity.into());
+    }
+
+    // Calculate amounts to withdraw
+    let amount_a = math::mul_div(lp_amount, reserve_a, supply)?;
+    let amount_b = math::mul_div(lp_amount, reserve_b, supply)?;
+    check_withdrawal_amounts(amount_a, amount_b, min_amount_a, min_amount_b)?;
+
+    // Burn LP tokens
     invoke(
-        &create_associated_token_account_instruction(
-            user_info.key,
+        &create_burn_instruction(
             user_lp_info.key,
-            user_info.key,
             lp_mint_info.key,
+            user_info.key,
+            lp_amount,
+        ),
+        &[
+            user_lp_info.clone(),
+            lp_mint_info.clone(),
+            user_info.clone(),
+            token_program_info.clone(),
+        ],
+    )?;
+
+    // Transfer tokens from vaults to user using vault PDA authorities
+    let vault_a_signer_seeds: &[&[_]] = &[
+        b"vault",
+        pool_info.key.as_ref(),
+        pool.token_a.as_ref(),
+        &[vault_a_bump],
+    ];
+
+    let vault_b_signer_seeds: &[&[_]] = &[
+        b"vault",
+        pool_info.key.as_ref(),
+        pool.token_b.as_ref(),
+        &[vault_b_bump],
+    ];
+
+    invoke_signed(
+        &create_transfer_instruction(
+            vault_a_info.key,
+            user_token_a_info.key,
+            vault_a_info.key,
+            amount_a,
         ),
         &[
-            user_info.clone(),
-            user_lp_info.clone(),
-            user_info.clone(),
-            lp_mint_info.clone(),
-            rent_info.clone(),
+            vault_a_info.clone(),
+            user_token_a_info.clone(),
+            vault_a_info.clone(),
             token_program_info.clone(),
-            system_program_info.clone(),
         ],
+        &[vault_a_signer_seeds],
     )?;
 
-    // Calculate liquidity
-    let liquidity: u64 = (amount_a as u128)
-        .checked_mul(amount_b as u128)
-        .unwrap()
-        .integer_sqrt() as u64;
-
-    // Mint LP tokens
     invoke_signed(
-        &create_mint_to_instruction(
-            lp_mint_info.key,
-            user_lp_info.key,
-            pool_info.key,
-            liquidity,
+        &create_transfer_instruction(
+            vault_b_info.key,
+            user_token_b_info.key,
+            vault_b_info.key,
+            amount_b,
         ),
         &[
-            lp_mint_info.clone(),
-            user_lp_info.clone(),
-            pool_info.clone(),
+            vault_b_info.clone(),
+            user_token_b_info.clone(),
+            vault_b_info.clone(),
             token_program_info.clone(),
         ],
-        &[pool_signer_seeds],
+        &[vault_b_signer_seeds],
     )?;
 
-    // Initialize pool state
-        let pool = Pool {
-            token_a: *token_a_info.key,
-            token_b: *token_b_info.key,
-            bump: _pool_bump,
-            reserve_a: amount_a,
-            reserve_b: amount_b,
-            total_lp_supply: liquidity,
-            fee_collected_a: 0,
-            fee_collected_b: 0,
-            fee_treasury: Pubkey::default(), // Will be set later via SetFeeTreasury
-        };
-        Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+    // Update pool state
+    pool.reserve_a = math::sub(pool.reserve_a, amount_a)?;
+    pool.reserve_b = math::sub(pool.reserve_b, amount_b)?;
+    pool.total_lp_supply = math::sub(pool.total_lp_supply, lp_amount)?;
+    pool.check_vault_balances(vault_a_info, vault_b_info)?;
+    
+    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
 
     Ok(())
 }
 
-fn process_add_liquidity(
+fn process_swap(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    amount_a: u64,
-    amount_b: u64,
+    swap_amount: SwapAmount,
+    direction_a_to_b: bool,
 ) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let pool_info = next_account_info(account_info_iter)?;
-    let token_a_info = next_account_info(account_info_iter)?;
-    let token_b_info = next_account_info(account_info_iter)?;
-    let vault_a_info = next_account_info(account_info_iter)?;
-    let vault_b_info = next_account_info(account_info_iter)?;
-    let lp_mint_info = next_account_info(account_info_iter)?;
-    let user_token_a_info = next_account_info(account_info_iter)?;
-    let user_token_b_info = next_account_info(account_info_iter)?;
-    let user_lp_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
+    let (config, accounts) = Config::split_from(program_id, accounts)?;
+    config.check_not_paused()?;
+    
+    let SwapAccounts {
+        pool_info,
+        token_a_info,
+        token_b_info,
+        vault_a_info,
+        vault_b_info,
+        user_in_info,
+        user_out_info,
+        user_info,
+        token_program_info,
+        system_program_info,
+    } = SwapAccounts::load(program_id, accounts, direction_a_to_b)?;
 
-    // Check if this is a native SOL pool add liquidity
+    // Check if this is a native SOL pool swap
     let is_native_sol_pool = *token_a_info.key == NATIVE_SOL_MINT || *token_b_info.key == NATIVE_SOL_MINT;
     
     if is_native_sol_pool {
-        // Native SOL pool add liquidity logic
-        solana_program::log::sol_log("AddLiquidityNativeSOL instruction called");
+        // Native SOL pool swap logic
+        solana_program::log::sol_log("Native SOL pool swap instruction called");
+        solana_program::log::sol_log(&format!("Swap amount: {:?}", swap_amount));
         
         // Determine which token is SOL and which is the SPL token
-        let (sol_amount, token_amount, token_mint) = if *token_a_info.key == NATIVE_SOL_MINT {
-            (amount_a, amount_b, *token_b_info.key)
+        let (is_sol_to_token, token_mint) = if *token_a_info.key == NATIVE_SOL_MINT {
+            (direction_a_to_b, *token_b_info.key)
     } else {
-            (amount_b, amount_a, *token_a_info.key)
+            (!direction_a_to_b, *token_a_info.key)
         };
 
-        solana_program::log::sol_log(&format!("SOL amount: {}", sol_amount));
-        solana_program::log::sol_log(&format!("Token amount: {}", token_amount));
-
         // Load pool state
         let mut pool = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+        pool.update_price_cumulative()?;
+        pool.check_active()?;
         
         // Verify pool seeds
         let (pool_pda, _pool_bump) = Pubkey::find_program_address(
-            &[b"native_sol_pool", &token_mint.as_ref()],
+            &[b"native_sol_pool", token_mint.as_ref()],
             program_id,
         );
         if pool_info.key != &pool_pda {
-            return Err(ProgramError::InvalidSeeds);
+            return Err(SwapError::InvalidPoolAddress.into());
         }
 
         // Verify pool token vault
         let (pool_token_vault_pda, _pool_token_vault_bump) = Pubkey::find_program_address(
-            &[b"native_sol_vault", pool_pda.as_ref(), &token_mint.as_ref()],
+            &[b"native_sol_vault", pool_pda.as_ref(), token_mint.as_ref()],
             program_id,
         );
         if vault_b_info.key != &pool_token_vault_pda {
-            return Err(ProgramError::InvalidSeeds);
+            return Err(SwapError::InvalidVault.into());
         }
-
-        // Verify LP mint
-        let (lp_mint_pda, _lp_mint_bump) = Pubkey::find_program_address(
-            &[b"native_sol_lp_mint", pool_pda.as_ref()],
-            program_id,
-        );
-        if lp_mint_info.key != &lp_mint_pda {
-            return Err(ProgramError::InvalidSeeds);
+        // Verify SOL vault
+        let (sol_vault_pda, sol_vault_bump) = get_sol_vault_address(&pool_pda, program_id);
+        if vault_a_info.key != &sol_vault_pda {
+            return Err(SwapError::InvalidVault.into());
         }
+        pool.reconcile(vault_a_info, vault_b_info)?;
 
-        // Calculate final amounts maintaining ratio
-        let (final_amount_sol, final_amount_token) = if pool.reserve_a > 0 && pool.reserve_b > 0 {
-            let required_token = (sol_amount as u128)
-                .checked_mul(pool.reserve_b as u128).unwrap()
-                .checked_div(pool.reserve_a as u128).unwrap() as u64;
-            if required_token <= token_amount {
-                (sol_amount, required_token)
-            } else {
-                let required_sol = (token_amount as u128)
-                    .checked_mul(pool.reserve_a as u128).unwrap()
-                    .checked_div(pool.reserve_b as u128).unwrap() as u64;
-                (required_sol, token_amount)
-            }
-        } else {
-            (sol_amount, token_amount)
-        };
-
-        // Transfer SOL to pool using system program
-        invoke(
-            &system_instruction::transfer(
-                user_info.key,
-                pool_info.key,
-                final_amount_sol,
-            ),
-            &[
-                user_info.clone(),
-                pool_info.clone(),
-                user_info.clone(), // system program
-            ],
-        )?;
-
-        // Transfer tokens from user to pool vault
-        invoke(
-            &create_transfer_instruction(
-                user_token_b_info.key,
-                vault_b_info.key,
-                user_info.key,
-                final_amount_token,
-            ),
-            &[
-                user_token_b_info.clone(),
-                vault_b_info.clone(),
-                user_info.clone(),
-                token_program_info.clone(),
-            ],
-        )?;
-
-        // Calculate liquidity to mint
-        let liquidity = if pool.total_lp_supply == 0 {
-            (final_amount_sol as u128)
-                .checked_mul(final_amount_token as u128).unwrap()
-                .integer_sqrt() as u64
-        } else {
-            (final_amount_sol as u128)
-                .checked_mul(pool.total_lp_supply as u128).unwrap()
-                .checked_div(pool.reserve_a as u128).unwrap() as u64
-        };
-
-        // Mint LP tokens
-        let pool_signer_seeds: &[&[_]] = &[
-            b"native_sol_pool",
-            &token_mint.as_ref(),
-            &[_pool_bump],
-        ];
-        
-        invoke_signed(
-            &create_mint_to_instruction(
-                lp_mint_info.key,
-                user_lp_info.key,
-                pool_info.key,
-                liquidity,
-            ),
-            &[
-                lp_mint_info.clone(),
-                user_lp_info.clone(),
-                pool_info.clone(),
-                token_program_info.clone(),
-            ],
-            &[pool_signer_seeds],
-        )?;
-
-        // Update pool state
-        pool.reserve_a = pool.reserve_a.checked_add(final_amount_sol).unwrap();
-        pool.reserve_b = pool.reserve_b.checked_add(final_amount_token).unwrap();
-        pool.total_lp_supply = pool.total_lp_supply.checked_add(liquidity).unwrap();
-        
-        // Pack updated pool state
-        let mut pool_data = pool_info.try_borrow_mut_data()?;
-        pool.pack_into_slice(&mut pool_data[..NativeSOLPool::LEN]);
-
-        // Refund unused tokens back to user
-        if sol_amount > final_amount_sol {
-            let excess_sol = sol_amount - final_amount_sol;
-            **pool_info.try_borrow_mut_lamports()? -= excess_sol;
-            **user_info.try_borrow_mut_lamports()? += excess_sol;
-        }
+        if is_sol_to_token {
+            // SOL to Token swap
+            solana_program::log::sol_log("SwapNativeSOLToToken instruction called");
+            
+            // Calculate swap amounts using constant product formula with the pool fee
+            let (amount_in, amount_out) = swap_amount.resolve(pool.reserve_a, pool.reserve_b, pool.fee_bps)?;
+
+            // Transfer SOL from user to the SOL vault, unwrapping it first if paid from a wSOL account
+            deposit_sol(
+                user_in_info,
+                user_info,
+                vault_a_info,
+                token_program_info,
+                system_program_info.ok_or(ProgramError::NotEnoughAccountKeys)?,
+                amount_in,
+            )?;
 
-        if token_amount > final_amount_token {
-            let excess_token = token_amount - final_amount_token;
+            // Transfer tokens from pool vault to user using vault PDA as authority
             let vault_signer_seeds: &[&[_]] = &[
                 b"native_sol_vault",
                 pool_pda.as_ref(),
-                &token_mint.as_ref(),
+                token_mint.as_ref(),
                 &[_pool_token_vault_bump],
             ];
-            
+
             invoke_signed(
                 &create_transfer_instruction(
                     vault_b_info.key,
-                    user_token_b_info.key,
+                    user_out_info.key,
                     vault_b_info.key,
-                    excess_token,
+                    amount_out,
                 ),
                 &[
                     vault_b_info.clone(),
-                    user_token_b_info.clone(),
+                    user_out_info.clone(),
                     vault_b_info.clone(),
                     token_program_info.clone(),
                 ],
                 &[vault_signer_seeds],
             )?;
-        }
-
-        solana_program::log::sol_log(&format!("Liquidity added: {} SOL, {} tokens, {} LP tokens minted", final_amount_sol, final_amount_token, liquidity));
-        
-        return Ok(());
-    }
-
-    let mut pool = Pool::unpack(&pool_info.data.borrow())?;
-    
-    // Store token addresses before pool is moved
-    let token_a = pool.token_a;
-    let token_b = pool.token_b;
-    
-    // Verify pool seeds
-    let (pool_pubkey, _) = Pubkey::find_program_address(
-        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-        program_id,
-    );
-    if pool_pubkey != *pool_info.key {
-        return Err(ProgramError::InvalidSeeds);
-    }
-
-    // Verify vault addresses and get bumps
-    let (vault_a_pubkey, vault_a_bump) = get_vault_address(pool_info.key, &token_a, program_id);
-    let (vault_b_pubkey, vault_b_bump) = get_vault_address(pool_info.key, &token_b, program_id);
-    
-    if vault_a_pubkey != *vault_a_info.key || vault_b_pubkey != *vault_b_info.key {
-        return Err(ProgramError::InvalidSeeds);
-    }
-
-    let reserve_a = pool.reserve_a;
-    let reserve_b = pool.reserve_b;
-    let supply = pool.total_lp_supply;
 
-    // Calculate final amounts maintaining ratio
-    let (final_amount_a, final_amount_b) = if reserve_a > 0 && reserve_b > 0 {
-        let required_b = (amount_a as u128)
-            .checked_mul(reserve_b as u128).unwrap()
-            .checked_div(reserve_a as u128).unwrap() as u64;
-        if required_b <= amount_b {
-            (amount_a, required_b)
-        } else {
-            let required_a = (amount_b as u128)
-                .checked_mul(reserve_a as u128).unwrap()
-                .checked_div(reserve_b as u128).unwrap() as u64;
-            (required_a, amount_b)
-        }
+            // Update pool reserves and accrue the protocol fee
+            let fee_amount = pool.apply_swap(true, amount_in, amount_out)?;
+            
+            solana_program::log::sol_log(&format!("Native SOL->Token fee collected: {}", fee_amount));
+            solana_program::log::sol_log(&format!("Swap completed: {} SOL -> {} tokens", amount_in, amount_out));
     } else {
-        (amount_a, amount_b)
-    };
+            // Token to SOL swap
+            solana_program::log::sol_log("SwapTokenToNativeSOL instruction called");
+            
+            // Calculate swap amounts using constant product formula with the pool fee
+            let (amount_in, amount_out) = swap_amount.resolve(pool.reserve_b, pool.reserve_a, pool.fee_bps)?;
 
-    // Transfer tokens to vaults
-    invoke(
-        &create_transfer_instruction(
-            user_token_a_info.key,
-            vault_a_info.key,
-            user_info.key,
-            final_amount_a,
-        ),
-        &[
-            user_token_a_info.clone(),
-            vault_a_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
-    )?;
+            // Transfer tokens from user to pool vault
+            transfer_to_vault(user_in_info, vault_b_info, user_info, token_program_info, amount_in)?;
+
+            // Transfer SOL from the SOL vault to user, as wSOL if a wSOL account was passed
+            withdraw_sol(
+                &pool_pda,
+                vault_a_info,
+                sol_vault_bump,
+                sol_destination(user_out_info, user_info),
+                token_program_info,
+                system_program_info.ok_or(ProgramError::NotEnoughAccountKeys)?,
+                amount_out,
+            )?;
 
-    invoke(
-        &create_transfer_instruction(
-            user_token_b_info.key,
-            vault_b_info.key,
-            user_info.key,
-            final_amount_b,
-        ),
-        &[
-            user_token_b_info.clone(),
-            vault_b_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
-    )?;
+            // Update pool reserves and accrue the protocol fee
+            let fee_amount = pool.apply_swap(false, amount_in, amount_out)?;
+            
+            solana_program::log::sol_log(&format!("Native Token->SOL fee collected: {}", fee_amount));
+            solana_program::log::sol_log(&format!("Swap completed: {} tokens -> {} SOL", amount_in, amount_out));
+        }
+        pool.check_balances(vault_a_info, vault_b_info)?;
+        
+        // Pack updated pool state
+        let mut pool_data = pool_info.try_borrow_mut_data()?;
+        pool.pack_into_slice(&mut pool_data[..NativeSOLPool::LEN]);
 
-    // Calculate liquidity to mint
-    let liquidity = if supply == 0 {
-        (final_amount_a as u128)
-            .checked_mul(final_amount_b as u128).unwrap()
-            .integer_sqrt() as u64
-    } else {
-        (final_amount_a as u128)
-            .checked_mul(supply as u128).unwrap()
-            .checked_div(reserve_a as u128).unwrap() as u64
-    };
+        return Ok(());
+    }
 
-    // Mint LP tokens
-    let pool_signer_seeds: &[&[_]] = &[
-        b"pool",
-        pool.token_a.as_ref(),
-        pool.token_b.as_ref(),
-        &[pool.bump],
-    ];
+    let mut pool = Pool::unpack(&pool_info.data.borrow())?;
+    pool.update_price_cumulative()?;
+    pool.check_active()?;
     
-    invoke_signed(
-        &create_mint_to_instruction(
-            lp_mint_info.key,
-            user_lp_info.key,
-            pool_info.key,
-            liquidity,
-        ),
-        &[
-            lp_mint_info.clone(),
-            user_lp_info.clone(),
-            pool_info.clone(),
-            token_program_info.clone(),
-        ],
-        &[pool_signer_seeds],
-    )?;
+    // Verify pool seeds
+    let (pool_pubkey, _) = Pubkey::find_program_address(
+        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
+        program_id,
+    );
+    if pool_pubkey != *pool_info.key {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
 
-    // Update pool state
-    pool.reserve_a = pool.reserve_a.checked_add(final_amount_a).unwrap();
-    pool.reserve_b = pool.reserve_b.checked_add(final_amount_b).unwrap();
-    pool.total_lp_supply = pool.total_lp_supply.checked_add(liquidity).unwrap();
+    // Verify vault addresses and get bumps
+    let (vault_a_pubkey, vault_a_bump) = get_vault_address(pool_info.key, &pool.token_a, program_id);
+    let (vault_b_pubkey, vault_b_bump) = get_vault_address(pool_info.key, &pool.token_b, program_id);
     
-    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+    if vault_a_pubkey != *vault_a_info.key || vault_b_pubkey != *vault_b_info.key {
+        return Err(SwapError::InvalidVault.into());
+    }
+    pool.reconcile(vault_a_info, vault_b_info)?;
 
-    // Refund unused tokens back to user (Uniswap pattern)
-    // If user provided more than the calculated final amount, return the excess
-    if amount_a > final_amount_a {
-        let excess_a = amount_a - final_amount_a;
-        let vault_a_signer_seeds: &[&[_]] = &[
+    // Calculate swap amounts (with the pool fee)
+    let (reserve_in, reserve_out) = if direction_a_to_b {
+        (pool.reserve_a, pool.reserve_b)
+    } else {
+        (pool.reserve_b, pool.reserve_a)
+    };
+    let (amount_in, amount_out) = swap_amount.resolve(reserve_in, reserve_out, pool.fee_bps)?;
+
+    // Transfer input tokens to vault
+    if direction_a_to_b {
+        transfer_to_vault(user_in_info, vault_a_info, user_info, token_program_info, amount_in)?;
+    } else {
+        transfer_to_vault(user_in_info, vault_b_info, user_info, token_program_info, amount_in)?;
+    }
+
+    // Transfer output tokens from vault to user using vault PDA as authority
+    if direction_a_to_b {
+        let vault_b_signer_seeds: &[&[_]] = &[
             b"vault",
             pool_info.key.as_ref(),
-            &token_a.as_ref(),
-            &[vault_a_bump],
+            pool.token_b.as_ref(),
+            &[vault_b_bump],
         ];
         
         invoke_signed(
             &create_transfer_instruction(
-                vault_a_info.key,
-                user_token_a_info.key,
-                vault_a_info.key,
-                excess_a,
+                vault_b_info.key,
+                user_out_info.key,
+                vault_b_info.key,
+                amount_out,
             ),
             &[
-                vault_a_info.clone(),
-                user_token_a_info.clone(),
-                vault_a_info.clone(),
+                vault_b_info.clone(),
+                user_out_info.clone(),
+                vault_b_info.clone(),
                 token_program_info.clone(),
             ],
-            &[vault_a_signer_seeds],
+            &[vault_b_signer_seeds],
         )?;
-    }
-
-    if amount_b > final_amount_b {
-        let excess_b = amount_b - final_amount_b;
-        let vault_b_signer_seeds: &[&[_]] = &[
+    } else {
+        let vault_a_signer_seeds: &[&[_]] = &[
             b"vault",
             pool_info.key.as_ref(),
-            &token_b.as_ref(),
-            &[vault_b_bump],
+            pool.token_a.as_ref(),
+            &[vault_a_bump],
         ];
         
         invoke_signed(
             &create_transfer_instruction(
-                vault_b_info.key,
-                user_token_b_info.key,
-                vault_b_info.key,
-                excess_b,
+                vault_a_info.key,
+                user_out_info.key,
+                vault_a_info.key,
+                amount_out,
             ),
             &[
-                vault_b_info.clone(),
-                user_token_b_info.clone(),
-                vault_b_info.clone(),
+                vault_a_info.clone(),
+                user_out_info.clone(),
+                vault_a_info.clone(),
                 token_program_info.clone(),
             ],
-            &[vault_b_signer_seeds],
+            &[vault_a_signer_seeds],
+        )?;
+    }
+
+    // Update pool reserves and accrue the protocol fee
+    let fee_amount = pool.apply_swap(direction_a_to_b, amount_in, amount_out)?;
+    
+    solana_program::log::sol_log(&format!("Fee collected: {} (direction_a_to_b: {})", fee_amount, direction_a_to_b));
+    pool.check_vault_balances(vault_a_info, vault_b_info)?;
+
+    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+// Route in the compact layout, one hop per full set of pool and vault accounts
+// passed. Each hop's direction follows from the mint arriving at it.
+fn process_multihop_swap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> ProgramResult {
+    let (config, accounts) = Config::split_from(program_id, accounts)?;
+    config.check_not_paused()?;
+    
+    // An optional trailing system program does not make up a full hop
+    let num_hops = accounts.len().saturating_sub(4) / 3;
+    if num_hops == 0 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let route_accounts = RouteAccounts::load_compact(program_id, accounts, num_hops)?;
+    let steps = load_route(program_id, &route_accounts)?;
+    
+    let swap_amount = SwapAmount::ExactIn { amount_in, minimum_amount_out, max_price_impact_bps: None };
+    execute_route(&route_accounts, steps, swap_amount)?;
+    
+    Ok(())
+}
+
+// Route of token_path.len() - 1 hops in the compact layout, which only needs the
+// user's source and destination accounts besides the pools and their vaults
+fn process_multihop_swap_with_path(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    token_path: Vec<Pubkey>,
+) -> ProgramResult {
+    let (config, accounts) = Config::split_from(program_id, accounts)?;
+    config.check_not_paused()?;
+    
+    if token_path.len() < 2 {
+        return Err(SwapError::InvalidSwapPath.into());
+    }
+    
+    let route_accounts = RouteAccounts::load_compact(program_id, accounts, token_path.len() - 1)?;
+    let steps = load_route(program_id, &route_accounts)?;
+    check_route_path(&steps, &token_path)?;
+    
+    let swap_amount = SwapAmount::ExactIn { amount_in, minimum_amount_out, max_price_impact_bps: None };
+    execute_route(&route_accounts, steps, swap_amount)?;
+    
+    Ok(())
+}
+
+fn process_multihop_swap_exact_out_with_path(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount_out: u64,
+    maximum_amount_in: u64,
+    token_path: Vec<Pubkey>,
+) -> ProgramResult {
+    let (config, accounts) = Config::split_from(program_id, accounts)?;
+    config.check_not_paused()?;
+    
+    if token_path.len() < 2 {
+        return Err(SwapError::InvalidSwapPath.into());
+    }
+    
+    let route_accounts = RouteAccounts::load_compact(program_id, accounts, token_path.len() - 1)?;
+    let steps = load_route(program_id, &route_accounts)?;
+    check_route_path(&steps, &token_path)?;
+    
+    let swap_amount = SwapAmount::ExactOut { amount_out, maximum_amount_in };
+    execute_route(&route_accounts, steps, swap_amount)?;
+    
+    Ok(())
+}
+
+// Route of 1..=MAX_ROUTE_HOPS hops described by account indices, so accounts
+// shared between hops are passed once
+fn process_multihop_swap_route(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    route: Vec<RouteHopDescriptor>,
+    destination: u8,
+) -> ProgramResult {
+    let (config, accounts) = Config::split_from(program_id, accounts)?;
+    config.check_not_paused()?;
+    
+    let route_accounts = RouteAccounts::load(program_id, accounts, &route, destination)?;
+    let steps = load_route(program_id, &route_accounts)?;
+    
+    let swap_amount = SwapAmount::ExactIn { amount_in, minimum_amount_out, max_price_impact_bps: None };
+    execute_route(&route_accounts, steps, swap_amount)?;
+    
+    Ok(())
+}
+
+// Input split over several routes in one instruction. Every route runs from the
+// same source into the same destination (in the compact layout, the hops of all
+// routes back to back) and only the total output is held to minimum_amount_out.
+fn process_swap_split(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    routes: Vec<SplitRoute>,
+) -> ProgramResult {
+    let (config, accounts) = Config::split_from(program_id, accounts)?;
+    config.check_not_paused()?;
+    
+    check_split_routes(&routes)?;
+    
+    let num_hops = routes.iter().map(|route| route.token_path.len() - 1).sum();
+    let mut split_accounts = RouteAccounts::load_compact(program_id, accounts, num_hops)?;
+    
+    // The first route to pay from a wSOL source would close it under the routes
+    // after it, so it is unwrapped once up front and every route pays from the wallet
+    if is_wrapped_sol_account(split_accounts.source_info) {
+        unwrap_sol(
+            split_accounts.source_info,
+            split_accounts.user_info,
+            split_accounts.token_program_info,
+            amount_in,
         )?;
+        split_accounts.source_info = split_accounts.user_info;
+    }
+    
+    let route_amounts = split_amounts(amount_in, &routes)?;
+    let mut total_out: u64 = 0;
+    let mut first_hop = 0;
+    for (index, (route, route_amount_in)) in routes.iter().zip(route_amounts).enumerate() {
+        let route_hops = first_hop..first_hop + route.token_path.len() - 1;
+        first_hop = route_hops.end;
+        if route_amount_in == 0 {
+            solana_program::log::sol_log(&format!("Split route {}: skipped, rounds down to 0", index));
+            continue;
+        }
+        
+        // Pools are loaded per route, so a pool shared with an earlier route is
+        // quoted at the reserves that route left behind
+        let route_accounts = split_accounts.route(route_hops)?;
+        let steps = load_route(program_id, &route_accounts)?;
+        check_route_path(&steps, &route.token_path)?;
+        
+        let swap_amount = SwapAmount::ExactIn { amount_in: route_amount_in, minimum_amount_out: 0, max_price_impact_bps: None };
+        let route_amount_out = execute_route(&route_accounts, steps, swap_amount)?;
+        total_out = math::add(total_out, route_amount_out)?;
+        
+        solana_program::log::sol_log(&format!("Split route {}: {} -> {}", index, route_amount_in, route_amount_out));
+    }
+    
+    if total_out < minimum_amount_out {
+        solana_program::log::sol_log(&format!("Slippage exceeded: {} < minimum {}", total_out, minimum_amount_out));
+        return Err(SwapError::SlippageExceeded.into());
+    }
+    
+    solana_program::log::sol_log(&format!("Split swap completed: {} -> {}", amount_in, total_out));
+    Ok(())
+}
+
+// Share of amount_in each route swaps. Non-final routes take their weight rounded
+// down, which is 0 for small inputs, and the last route takes the remainder.
+fn split_amounts(amount_in: u64, routes: &[SplitRoute]) -> Result<Vec<u64>, ProgramError> {
+    let mut remaining_in = amount_in;
+    let mut amounts = Vec::with_capacity(routes.len());
+    for route in &routes[..routes.len().saturating_sub(1)] {
+        let route_amount_in = math::mul_div(amount_in, route.weight_bps as u64, BPS_DENOMINATOR)?;
+        remaining_in = math::sub(remaining_in, route_amount_in)?;
+        amounts.push(route_amount_in);
+    }
+    amounts.push(remaining_in);
+    Ok(amounts)
+}
+
+// Longest route a multihop swap may take, bounded by the compute budget
+const MAX_ROUTE_HOPS: usize = 4;
+
+// Most routes and hops (across all routes) a SwapSplit may take
+const MAX_SPLIT_ROUTES: usize = 4;
+const MAX_SPLIT_HOPS: usize = 8;
+
+// Routes must share their first and last mint, and their weights must cover the
+// whole input
+fn check_split_routes(routes: &[SplitRoute]) -> ProgramResult {
+    if routes.is_empty() || routes.len() > MAX_SPLIT_ROUTES {
+        solana_program::log::sol_log(&format!("Split must have 1 to {} routes, got {}", MAX_SPLIT_ROUTES, routes.len()));
+        return Err(SwapError::InvalidSwapPath.into());
+    }
+    
+    let first_path = &routes[0].token_path;
+    if first_path.len() < 2 {
+        return Err(SwapError::InvalidSwapPath.into());
+    }
+    let mut num_hops = 0;
+    let mut total_weight: u64 = 0;
+    for route in routes {
+        let path = &route.token_path;
+        if path.len() < 2 || path[0] != first_path[0] || path[path.len() - 1] != first_path[first_path.len() - 1] {
+            return Err(SwapError::InvalidSwapPath.into());
+        }
+        num_hops += path.len() - 1;
+        
+        if route.weight_bps == 0 {
+            return Err(SwapError::InvalidSplitWeights.into());
+        }
+        total_weight += route.weight_bps as u64;
+    }
+    
+    if num_hops > MAX_SPLIT_HOPS {
+        solana_program::log::sol_log(&format!("Split has {} hops, at most {} allowed", num_hops, MAX_SPLIT_HOPS));
+        return Err(SwapError::InvalidSwapPath.into());
+    }
+    if total_weight != BPS_DENOMINATOR {
+        solana_program::log::sol_log(&format!("Split weights add up to {} bps, expected {}", total_weight, BPS_DENOMINATOR));
+        return Err(SwapError::InvalidSplitWeights.into());
+    }
+    Ok(())
+}
+
+// Pool state of one hop. Native SOL pools keep SOL as token A.
+enum RoutePool {
+    Token(Pool),
+    NativeSol(NativeSOLPool),
+}
+
+impl RoutePool {
+    fn reserves(&self, direction_a_to_b: bool) -> (u64, u64) {
+        let (reserve_a, reserve_b) = match self {
+            RoutePool::Token(pool) => (pool.reserve_a, pool.reserve_b),
+            RoutePool::NativeSol(pool) => (pool.reserve_a, pool.reserve_b),
+        };
+        if direction_a_to_b {
+            (reserve_a, reserve_b)
+        } else {
+            (reserve_b, reserve_a)
+        }
+    }
+
+    fn fee_bps(&self) -> u16 {
+        match self {
+            RoutePool::Token(pool) => pool.fee_bps,
+            RoutePool::NativeSol(pool) => pool.fee_bps,
+        }
+    }
+}
+
+// A route hop with its pool state loaded and reconciled, and the mints it swaps between
+struct RouteStep {
+    pool: RoutePool,
+    direction_a_to_b: bool,
+    mint_in: Pubkey,
+    mint_out: Pubkey,
+    vault_out_bump: u8,
+}
+
+// Load a hop's pool, of either type, and check its address and vaults. Native
+// pools take the SOL vault as vault A and the token vault as vault B.
+// Returns the pool with the bumps of vault A and vault B.
+fn load_route_pool(program_id: &Pubkey, route_hop: &RouteHop) -> Result<(RoutePool, u8, u8), ProgramError> {
+    let RouteHop {
+        pool_info,
+        vault_a_info,
+        vault_b_info,
+        ..
+    } = *route_hop;
+    
+    match AccountType::of(pool_info)? {
+        AccountType::Pool => {
+            let mut pool = Pool::unpack(&pool_info.data.borrow())?;
+            pool.update_price_cumulative()?;
+            pool.check_active()?;
+            
+            let (pool_pubkey, _) = Pubkey::find_program_address(
+                &[b"pool", pool.token_a.as_ref(), pool.token_b.as_ref()],
+                program_id,
+            );
+            if pool_pubkey != *pool_info.key {
+                return Err(SwapError::InvalidPoolAddress.into());
+            }
+            
+            check_owner(vault_a_info, &gorbchain_token_program_id())?;
+            check_owner(vault_b_info, &gorbchain_token_program_id())?;
+            let (vault_a_pubkey, vault_a_bump) = get_vault_address(pool_info.key, &pool.token_a, program_id);
+            let (vault_b_pubkey, vault_b_bump) = get_vault_address(pool_info.key, &pool.token_b, program_id);
+            if vault_a_pubkey != *vault_a_info.key || vault_b_pubkey != *vault_b_info.key {
+                return Err(SwapError::InvalidVault.into());
+            }
+            pool.reconcile(vault_a_info, vault_b_info)?;
+            
+            Ok((RoutePool::Token(pool), vault_a_bump, vault_b_bump))
+        }
+        AccountType::NativeSOLPool => {
+            let mut pool = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+            pool.update_price_cumulative()?;
+            pool.check_active()?;
+            
+            let (pool_pubkey, _) = Pubkey::find_program_address(
+                &[b"native_sol_pool", pool.token_mint.as_ref()],
+                program_id,
+            );
+            if pool_pubkey != *pool_info.key {
+                return Err(SwapError::InvalidPoolAddress.into());
+            }
+            
+            check_owner(vault_a_info, &solana_program::system_program::id())?;
+            check_owner(vault_b_info, &gorbchain_token_program_id())?;
+            let (sol_vault_pubkey, sol_vault_bump) = get_sol_vault_address(pool_info.key, program_id);
+            let (vault_pubkey, vault_bump) = Pubkey::find_program_address(
+                &[b"native_sol_vault", pool_info.key.as_ref(), pool.token_mint.as_ref()],
+                program_id,
+            );
+            if sol_vault_pubkey != *vault_a_info.key || vault_pubkey != *vault_b_info.key {
+                return Err(SwapError::InvalidVault.into());
+            }
+            pool.reconcile(vault_a_info, vault_b_info)?;
+            
+            Ok((RoutePool::NativeSol(pool), sol_vault_bump, vault_bump))
+        }
+        _ => Err(SwapError::PoolTypeMismatch.into()),
+    }
+}
+
+// Mint held by an account on the route. SOL may travel as lamports in the user's
+// wallet or as wSOL, both count as the native SOL mint.
+fn route_account_mint(info: &AccountInfo, user_info: &AccountInfo) -> Result<Pubkey, ProgramError> {
+    if info.key == user_info.key {
+        return Ok(NATIVE_SOL_MINT);
+    }
+    check_owner(info, &gorbchain_token_program_id())?;
+    Ok(unpack_token_account(info)?.mint)
+}
+
+// Validate every hop of a route before anything moves. The first hop takes the
+// mint of the user's source account and each hop must take the mint the previous
+// one paid out, which also fixes the swap direction of every pool.
+fn load_route(program_id: &Pubkey, route_accounts: &RouteAccounts) -> Result<Vec<RouteStep>, ProgramError> {
+    let hops = &route_accounts.hops;
+    if hops.is_empty() || hops.len() > MAX_ROUTE_HOPS {
+        solana_program::log::sol_log(&format!("Route must have 1 to {} hops, got {}", MAX_ROUTE_HOPS, hops.len()));
+        return Err(SwapError::InvalidSwapPath.into());
+    }
+    
+    let mut mint_in = route_account_mint(route_accounts.source_info, route_accounts.user_info)?;
+    let mut steps: Vec<RouteStep> = Vec::with_capacity(hops.len());
+    for (hop, route_hop) in hops.iter().enumerate() {
+        let pool_info = route_hop.pool_info;
+        
+        // A pool may only appear once, its reserves are quoted before any hop executes
+        if hops[..hop].iter().any(|previous| previous.pool_info.key == pool_info.key) {
+            return Err(SwapError::InvalidSwapPath.into());
+        }
+        
+        let (pool, vault_a_bump, vault_b_bump) = load_route_pool(program_id, route_hop)?;
+        let (token_a, token_b) = match &pool {
+            RoutePool::Token(pool) => (pool.token_a, pool.token_b),
+            RoutePool::NativeSol(pool) => (NATIVE_SOL_MINT, pool.token_mint),
+        };
+        
+        let (direction_a_to_b, mint_out, vault_out_bump) = if mint_in == token_a {
+            (true, token_b, vault_b_bump)
+        } else if mint_in == token_b {
+            (false, token_a, vault_a_bump)
+        } else {
+            solana_program::log::sol_log(&format!("Hop {}: pool {} does not trade {}", hop, pool_info.key, mint_in));
+            return Err(SwapError::InvalidTokenMint.into());
+        };
+        
+        steps.push(RouteStep {
+            pool,
+            direction_a_to_b,
+            mint_in,
+            mint_out,
+            vault_out_bump,
+        });
+        mint_in = mint_out;
+    }
+    
+    let destination_info = route_accounts.destination_info;
+    if route_account_mint(destination_info, route_accounts.user_info)? != mint_in {
+        solana_program::log::sol_log(&format!("Destination {} does not hold {}", destination_info.key, mint_in));
+        return Err(SwapError::InvalidTokenMint.into());
+    }
+    
+    // SOL legs move through the system program
+    if steps.iter().any(|step| matches!(step.pool, RoutePool::NativeSol(_))) && route_accounts.system_program_info.is_none() {
+        solana_program::log::sol_log("Route through a native SOL pool needs the system program");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    
+    Ok(steps)
+}
+
+// Route must follow the caller's token path exactly
+fn check_route_path(steps: &[RouteStep], token_path: &[Pubkey]) -> ProgramResult {
+    if token_path.len() != steps.len() + 1 {
+        return Err(SwapError::InvalidSwapPath.into());
+    }
+    for (hop, step) in steps.iter().enumerate() {
+        if step.mint_in != token_path[hop] || step.mint_out != token_path[hop + 1] {
+            return Err(SwapError::InvalidSwapPath.into());
+        }
     }
-
     Ok(())
 }
 
-fn process_remove_liquidity(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    lp_amount: u64,
-) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let pool_info = next_account_info(account_info_iter)?;
-    let token_a_info = next_account_info(account_info_iter)?;
-    let token_b_info = next_account_info(account_info_iter)?;
-    let vault_a_info = next_account_info(account_info_iter)?;
-    let vault_b_info = next_account_info(account_info_iter)?;
-    let lp_mint_info = next_account_info(account_info_iter)?;
-    let user_lp_info = next_account_info(account_info_iter)?;
-    let user_token_a_info = next_account_info(account_info_iter)?;
-    let user_token_b_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
+// Amount entering each hop: amounts[hop] is the input of that hop and
+// amounts[hop + 1] its output, amounts[0] is what the user pays
+fn quote_route(steps: &[RouteStep], swap_amount: SwapAmount) -> Result<Vec<u64>, ProgramError> {
+    let mut amounts = vec![0u64; steps.len() + 1];
+    match swap_amount {
+        SwapAmount::ExactIn { amount_in, minimum_amount_out, .. } => {
+            amounts[0] = amount_in;
+            for (hop, step) in steps.iter().enumerate() {
+                let (reserve_in, reserve_out) = step.pool.reserves(step.direction_a_to_b);
+                amounts[hop + 1] = calculate_swap_output(amounts[hop], reserve_in, reserve_out, step.pool.fee_bps())?;
+            }
+            if amounts[steps.len()] < minimum_amount_out {
+                solana_program::log::sol_log(&format!("Slippage exceeded: {} < minimum {}", amounts[steps.len()], minimum_amount_out));
+                return Err(SwapError::SlippageExceeded.into());
+            }
+        }
+        SwapAmount::ExactOut { amount_out, maximum_amount_in } => {
+            // Walk backwards so that the last hop delivers exactly amount_out
+            amounts[steps.len()] = amount_out;
+            for (hop, step) in steps.iter().enumerate().rev() {
+                let (reserve_in, reserve_out) = step.pool.reserves(step.direction_a_to_b);
+                amounts[hop] = calculate_swap_input(amounts[hop + 1], reserve_in, reserve_out, step.pool.fee_bps())?;
+            }
+            if amounts[0] > maximum_amount_in {
+                solana_program::log::sol_log(&format!("Slippage exceeded: {} > maximum {}", amounts[0], maximum_amount_in));
+                return Err(SwapError::SlippageExceeded.into());
+            }
+        }
+    }
+    
+    Ok(amounts)
+}
 
-    // Check if this is a native SOL pool remove liquidity
-    let is_native_sol_pool = *token_a_info.key == NATIVE_SOL_MINT || *token_b_info.key == NATIVE_SOL_MINT;
+// Execute a loaded route. The user pays the first hop; every hop after that is
+// paid by the previous pool, straight from its output vault into the next pool's
+// input vault with the vault PDA signing. The last hop pays the user's destination.
+// SOL moves as lamports, unwrapping and syncing wSOL accounts of the user on the way.
+// Returns the amount delivered by the last hop.
+fn execute_route(route_accounts: &RouteAccounts, steps: Vec<RouteStep>, swap_amount: SwapAmount) -> Result<u64, ProgramError> {
+    let amounts = quote_route(&steps, swap_amount)?;
+    let user_info = route_accounts.user_info;
+    let token_program_info = route_accounts.token_program_info;
+    let hops = &route_accounts.hops;
     
-    if is_native_sol_pool {
-        // Native SOL pool remove liquidity logic
-        solana_program::log::sol_log("RemoveLiquidityNativeSOL instruction called");
-        solana_program::log::sol_log(&format!("LP amount: {}", lp_amount));
+    let vaults_in: Vec<&AccountInfo> = steps
+        .iter()
+        .zip(hops.iter())
+        .map(|(step, hop)| if step.direction_a_to_b { hop.vault_a_info } else { hop.vault_b_info })
+        .collect();
+    
+    // The first hop is paid from the user's source, from the wallet or unwrapped from wSOL for SOL
+    if steps[0].mint_in == NATIVE_SOL_MINT {
+        deposit_sol(
+            route_accounts.source_info,
+            user_info,
+            vaults_in[0],
+            token_program_info,
+            route_accounts.system_program_info.ok_or(ProgramError::NotEnoughAccountKeys)?,
+            amounts[0],
+        )?;
+    } else {
+        transfer_to_vault(route_accounts.source_info, vaults_in[0], user_info, token_program_info, amounts[0])?;
+    }
+    
+    let last_hop = steps.len() - 1;
+    for (hop, (step, route_hop)) in steps.into_iter().zip(hops.iter()).enumerate() {
+        let RouteHop {
+            pool_info,
+            vault_a_info,
+            vault_b_info,
+        } = *route_hop;
+        let vault_out = if step.direction_a_to_b { vault_b_info } else { vault_a_info };
+        let hop_amount_in = amounts[hop];
+        let hop_amount_out = amounts[hop + 1];
         
-        // Determine which token is SOL and which is the SPL token
-        let token_mint = if *token_a_info.key == NATIVE_SOL_MINT {
-            *token_b_info.key
+        if step.mint_out == NATIVE_SOL_MINT {
+            // SOL vault of a native pool, paid out through the system program
+            let system_program_info = route_accounts
+                .system_program_info
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            if hop == last_hop {
+                withdraw_sol(
+                    pool_info.key,
+                    vault_out,
+                    step.vault_out_bump,
+                    route_accounts.destination_info,
+                    token_program_info,
+                    system_program_info,
+                    hop_amount_out,
+                )?;
+            } else {
+                transfer_from_sol_vault(pool_info.key, vault_out, step.vault_out_bump, vaults_in[hop + 1], system_program_info, hop_amount_out)?;
+            }
         } else {
-            *token_a_info.key
+            // Transfer output tokens using vault PDA as authority
+            let vault_seed: &[u8] = match step.pool {
+                RoutePool::Token(_) => b"vault",
+                RoutePool::NativeSol(_) => b"native_sol_vault",
+            };
+            let vault_out_signer_seeds: &[&[_]] = &[
+                vault_seed,
+                pool_info.key.as_ref(),
+                step.mint_out.as_ref(),
+                &[step.vault_out_bump],
+            ];
+            
+            if hop == last_hop {
+                invoke_signed(
+                    &create_transfer_instruction(
+                        vault_out.key,
+                        route_accounts.destination_info.key,
+                        vault_out.key,
+                        hop_amount_out,
+                    ),
+                    &[
+                        vault_out.clone(),
+                        route_accounts.destination_info.clone(),
+                        vault_out.clone(),
+                        token_program_info.clone(),
+                    ],
+                    &[vault_out_signer_seeds],
+                )?;
+            } else {
+                transfer_to_vault_signed(vault_out, vaults_in[hop + 1], vault_out, token_program_info, hop_amount_out, &[vault_out_signer_seeds])?;
+            }
+        }
+        
+        // Update pool reserves and accrue the protocol fee
+        let fee_amount = match step.pool {
+            RoutePool::Token(mut pool) => {
+                let fee_amount = pool.apply_swap(step.direction_a_to_b, hop_amount_in, hop_amount_out)?;
+                pool.check_vault_balances(vault_a_info, vault_b_info)?;
+                
+                Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+                fee_amount
+            }
+            RoutePool::NativeSol(mut pool) => {
+                let fee_amount = pool.apply_swap(step.direction_a_to_b, hop_amount_in, hop_amount_out)?;
+                pool.check_balances(vault_a_info, vault_b_info)?;
+                
+                let mut pool_data = pool_info.try_borrow_mut_data()?;
+                pool.pack_into_slice(&mut pool_data[..NativeSOLPool::LEN]);
+                fee_amount
+            }
         };
-
-        // Load pool state
-    let mut pool = NativeSOLPool::unpack(&pool_info.data.borrow())?;
-    
-    // Verify pool seeds
-        let (pool_pda, _pool_bump) = Pubkey::find_program_address(
-            &[b"native_sol_pool", &token_mint.as_ref()],
-        program_id,
-    );
-        if pool_info.key != &pool_pda {
-        return Err(ProgramError::InvalidSeeds);
+        
+        solana_program::log::sol_log(&format!("Multihop hop {}: {} {} -> {} {}, fee collected: {}", hop, hop_amount_in, step.mint_in, hop_amount_out, step.mint_out, fee_amount));
     }
+    
+    Ok(amounts[amounts.len() - 1])
+}
 
-    // Verify pool token vault
-    let (pool_token_vault_pda, _pool_token_vault_bump) = Pubkey::find_program_address(
-            &[b"native_sol_vault", pool_pda.as_ref(), &token_mint.as_ref()],
-        program_id,
-    );
-        if vault_b_info.key != &pool_token_vault_pda {
-        return Err(ProgramError::InvalidSeeds);
-    }
+// Swap fee tiers a pool can be created with, in basis points of the input amount
+const FEE_TIERS_BPS: [u16; 4] = [1, 5, 30, 100];
+const BPS_DENOMINATOR: u64 = 10_000;
 
-    // Verify LP mint
-    let (lp_mint_pda, _lp_mint_bump) = Pubkey::find_program_address(
-            &[b"native_sol_lp_mint", pool_pda.as_ref()],
-        program_id,
-    );
-    if lp_mint_info.key != &lp_mint_pda {
-        return Err(ProgramError::InvalidSeeds);
+fn check_fee_tier(fee_bps: u16) -> ProgramResult {
+    if !FEE_TIERS_BPS.contains(&fee_bps) {
+        solana_program::log::sol_log(&format!("Invalid fee tier: {} bps", fee_bps));
+        return Err(SwapError::InvalidFeeTier.into());
     }
+    Ok(())
+}
 
-        let sol_reserve = pool.reserve_a;
-        let token_reserve = pool.reserve_b;
-        let supply = pool.total_lp_supply;
-
-        // Calculate amounts to withdraw
-        let amount_sol = (lp_amount as u128)
-            .checked_mul(sol_reserve as u128).unwrap()
-            .checked_div(supply as u128).unwrap() as u64;
-        let amount_token = (lp_amount as u128)
-            .checked_mul(token_reserve as u128).unwrap()
-            .checked_div(supply as u128).unwrap() as u64;
+// Helper function to calculate output amount for a single swap
+fn calculate_swap_output(
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    fee_bps: u16,
+) -> Result<u64, ProgramError> {
+    if amount_in == 0 {
+        return Err(SwapError::ZeroAmount.into());
+    }
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(SwapError::ZeroLiquidity.into());
+    }
+    
+    let amount_in_with_fee = math::wide_mul(amount_in, BPS_DENOMINATOR - fee_bps as u64);
+    let numerator = math::mul_wide(amount_in_with_fee, reserve_out)?;
+    let denominator = math::add_wide(math::wide_mul(reserve_in, BPS_DENOMINATOR), amount_in_with_fee)?;
+    
+    math::to_u64(math::div_wide(numerator, denominator)?)
+}
 
-        // Burn LP tokens
-    invoke(
-            &create_burn_instruction(
-                user_lp_info.key,
-                lp_mint_info.key,
-            user_info.key,
-                lp_amount,
-        ),
-        &[
-                user_lp_info.clone(),
-                lp_mint_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
-    )?;
+// Helper function to calculate the input amount required to receive exactly
+// amount_out from a single swap (inverse of calculate_swap_output, rounded up)
+fn calculate_swap_input(
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    fee_bps: u16,
+) -> Result<u64, ProgramError> {
+    if amount_out == 0 {
+        return Err(SwapError::ZeroAmount.into());
+    }
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(SwapError::ZeroLiquidity.into());
+    }
+    if amount_out >= reserve_out {
+        return Err(SwapError::InsufficientLiquidity.into());
+    }
 
-        // Transfer SOL from pool to user
-        **pool_info.try_borrow_mut_lamports()? -= amount_sol;
-        **user_info.try_borrow_mut_lamports()? += amount_sol;
+    let numerator = math::mul_wide(math::wide_mul(reserve_in, amount_out), BPS_DENOMINATOR)?;
+    let denominator = math::wide_mul(math::sub(reserve_out, amount_out)?, BPS_DENOMINATOR - fee_bps as u64);
 
-        // Transfer tokens from pool vault to user using vault PDA as authority
-        let vault_signer_seeds: &[&[_]] = &[
-            b"native_sol_vault",
-            pool_pda.as_ref(),
-            &token_mint.as_ref(),
-            &[_pool_token_vault_bump],
-        ];
-        
-        invoke_signed(
-            &create_transfer_instruction(
-                vault_b_info.key,
-                user_token_b_info.key,
-                vault_b_info.key,
-                amount_token,
-            ),
-            &[
-                vault_b_info.clone(),
-                user_token_b_info.clone(),
-                vault_b_info.clone(),
-                token_program_info.clone(),
-            ],
-            &[vault_signer_seeds],
-        )?;
+    math::to_u64(math::div_wide_ceil(numerator, denominator)?)
+}
 
-        // Update pool state
-        pool.reserve_a = pool.reserve_a.checked_sub(amount_sol).unwrap();
-        pool.reserve_b = pool.reserve_b.checked_sub(amount_token).unwrap();
-        pool.total_lp_supply = pool.total_lp_supply.checked_sub(lp_amount).unwrap();
-        
-        // Pack updated pool state
-        let mut pool_data = pool_info.try_borrow_mut_data()?;
-        pool.pack_into_slice(&mut pool_data[..NativeSOLPool::LEN]);
+// The protocol takes 1/divisor of the swap fee, the rest stays in the reserves for LPs.
+// A divisor of 0 turns the protocol fee off; otherwise it is at least 2 so LPs always
+// keep at least half of the fee.
+const MIN_PROTOCOL_FEE_DIVISOR: u8 = 2;
 
-        solana_program::log::sol_log(&format!("Liquidity removed: {} LP tokens -> {} SOL, {} tokens", lp_amount, amount_sol, amount_token));
-        
-        return Ok(());
+fn check_protocol_fee_divisor(protocol_fee_divisor: u8) -> ProgramResult {
+    if protocol_fee_divisor != 0 && protocol_fee_divisor < MIN_PROTOCOL_FEE_DIVISOR {
+        solana_program::log::sol_log(&format!("Invalid protocol fee divisor: {}", protocol_fee_divisor));
+        return Err(SwapError::InvalidProtocolFee.into());
     }
+    Ok(())
+}
 
-    let mut pool = Pool::unpack(&pool_info.data.borrow())?;
-    
-    // Verify pool seeds
-    let (pool_pubkey, _) = Pubkey::find_program_address(
-        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-        program_id,
-    );
-    if pool_pubkey != *pool_info.key {
-        return Err(ProgramError::InvalidSeeds);
+// Split a swap input into the amount credited to the input reserve and the protocol fee
+fn split_protocol_fee(amount_in: u64, fee_bps: u16, protocol_fee_divisor: u8) -> Result<(u64, u64), ProgramError> {
+    if protocol_fee_divisor == 0 {
+        return Ok((amount_in, 0));
     }
+    let swap_fee = math::mul_div(amount_in, fee_bps as u64, BPS_DENOMINATOR)?;
+    let protocol_fee = swap_fee / protocol_fee_divisor as u64;
+    let reserve_credit = math::sub(amount_in, protocol_fee)?;
+    Ok((reserve_credit, protocol_fee))
+}
 
-    // Verify vault addresses and get bumps
-    let (vault_a_pubkey, vault_a_bump) = get_vault_address(pool_info.key, &pool.token_a, program_id);
-    let (vault_b_pubkey, vault_b_bump) = get_vault_address(pool_info.key, &pool.token_b, program_id);
-    
-    if vault_a_pubkey != *vault_a_info.key || vault_b_pubkey != *vault_b_info.key {
-        return Err(ProgramError::InvalidSeeds);
+// The vault must hold at least reserve + fees. A larger balance (e.g. a direct
+// transfer into the vault) is tolerated, otherwise a donation could brick the pool.
+fn check_vault_balance(vault_info: &AccountInfo, reserve: u64, fees: u64) -> ProgramResult {
+    let balance = vault_balance(vault_info)?;
+    let required = math::add(reserve, fees)?;
+    if balance < required {
+        solana_program::log::sol_log(&format!("Vault {} balance {} below reserve + fees {}", vault_info.key, balance, required));
+        return Err(SwapError::VaultBalanceMismatch.into());
     }
+    Ok(())
+}
 
-    let reserve_a = pool.reserve_a;
-    let reserve_b = pool.reserve_b;
-    let supply = pool.total_lp_supply;
-
-    // Calculate amounts to withdraw
-    let amount_a = (lp_amount as u128)
-        .checked_mul(reserve_a as u128).unwrap()
-        .checked_div(supply as u128).unwrap() as u64;
-    let amount_b = (lp_amount as u128)
-        .checked_mul(reserve_b as u128).unwrap()
-        .checked_div(supply as u128).unwrap() as u64;
+fn vault_balance(vault_info: &AccountInfo) -> Result<u64, ProgramError> {
+    Ok(unpack_token_account(vault_info)?.amount)
+}
 
-    // Burn LP tokens
-    invoke(
-        &create_burn_instruction(
-            user_lp_info.key,
-            lp_mint_info.key,
-            user_info.key,
-            lp_amount,
-        ),
-        &[
-            user_lp_info.clone(),
-            lp_mint_info.clone(),
-            user_info.clone(),
-            token_program_info.clone(),
-        ],
-    )?;
+// Reserve backed by a vault balance: everything that is not an unclaimed protocol fee
+fn reserve_from_balance(balance: u64, fees: u64) -> Result<u64, ProgramError> {
+    if balance < fees {
+        solana_program::log::sol_log(&format!("Balance {} below unclaimed fees {}", balance, fees));
+        return Err(SwapError::VaultBalanceMismatch.into());
+    }
+    math::sub(balance, fees)
+}
 
-    // Transfer tokens from vaults to user using vault PDA authorities
-    let vault_a_signer_seeds: &[&[_]] = &[
-        b"vault",
-        pool_info.key.as_ref(),
-        pool.token_a.as_ref(),
-        &[vault_a_bump],
-    ];
+// Balance a vault holds above its reserve and unclaimed fees, which Skim pays out
+fn vault_excess(balance: u64, reserve: u64, fees: u64) -> Result<u64, ProgramError> {
+    math::sub(balance, math::add(reserve, fees)?).map_err(|_| SwapError::VaultBalanceMismatch.into())
+}
 
-    let vault_b_signer_seeds: &[&[_]] = &[
-        b"vault",
-        pool_info.key.as_ref(),
-        pool.token_b.as_ref(),
-        &[vault_b_bump],
-    ];
+// Deposit into a vault and check that the vault was credited the full amount.
+// Tokens that take a cut on transfer would otherwise leave reserves overstated.
+fn transfer_to_vault<'info>(
+    source_info: &AccountInfo<'info>,
+    vault_info: &AccountInfo<'info>,
+    authority_info: &AccountInfo<'info>,
+    token_program_info: &AccountInfo<'info>,
+    amount: u64,
+) -> ProgramResult {
+    transfer_to_vault_signed(source_info, vault_info, authority_info, token_program_info, amount, &[])
+}
 
+// Same as transfer_to_vault, for a source whose authority is a PDA of this program
+// (another pool's vault paying the next hop of a route)
+fn transfer_to_vault_signed<'info>(
+    source_info: &AccountInfo<'info>,
+    vault_info: &AccountInfo<'info>,
+    authority_info: &AccountInfo<'info>,
+    token_program_info: &AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let balance_before = vault_balance(vault_info)?;
     invoke_signed(
         &create_transfer_instruction(
-            vault_a_info.key,
-            user_token_a_info.key,
-            vault_a_info.key,
-            amount_a,
+            source_info.key,
+            vault_info.key,
+            authority_info.key,
+            amount,
         ),
         &[
-            vault_a_info.clone(),
-            user_token_a_info.clone(),
-            vault_a_info.clone(),
+            source_info.clone(),
+            vault_info.clone(),
+            authority_info.clone(),
             token_program_info.clone(),
         ],
-        &[vault_a_signer_seeds],
+        signer_seeds,
     )?;
+    let received = math::sub(vault_balance(vault_info)?, balance_before)?;
+    if received != amount {
+        solana_program::log::sol_log(&format!("Vault {} received {} of {} transferred", vault_info.key, received, amount));
+        return Err(SwapError::TransferAmountMismatch.into());
+    }
+    Ok(())
+}
 
-    invoke_signed(
-        &create_transfer_instruction(
-            vault_b_info.key,
-            user_token_b_info.key,
-            vault_b_info.key,
-            amount_b,
-        ),
-        &[
-            vault_b_info.clone(),
-            user_token_b_info.clone(),
-            vault_b_info.clone(),
-            token_program_info.clone(),
-        ],
-        &[vault_b_signer_seeds],
-    )?;
+// Price impact of a swap in basis points: how far the execution price falls
+// short of the pre-trade spot price reserve_out / reserve_in (includes the fee)
+fn calculate_price_impact_bps(
+    amount_in: u64,
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+) -> Result<u64, ProgramError> {
+    if reserve_in == 0 {
+        return Err(SwapError::ZeroLiquidity.into());
+    }
 
-    // Update pool state
-    pool.reserve_a = pool.reserve_a.checked_sub(amount_a).unwrap();
-    pool.reserve_b = pool.reserve_b.checked_sub(amount_b).unwrap();
-    pool.total_lp_supply = pool.total_lp_supply.checked_sub(lp_amount).unwrap();
-    
-    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+    let spot_amount_out = math::div_wide(math::wide_mul(amount_in, reserve_out), reserve_in as u128)?;
+    if spot_amount_out == 0 {
+        return Ok(0);
+    }
+
+    let shortfall = spot_amount_out.saturating_sub(amount_out as u128);
+    math::to_u64(math::div_wide(math::mul_wide(shortfall, BPS_DENOMINATOR)?, spot_amount_out)?)
+}
+
+// Reject a swap whose output is below the caller's minimum or whose price
+// impact exceeds the caller's limit
+fn check_swap_limits(
+    amount_in: u64,
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    minimum_amount_out: u64,
+    max_price_impact_bps: Option<u16>,
+) -> ProgramResult {
+    if amount_out < minimum_amount_out {
+        solana_program::log::sol_log(&format!("Slippage exceeded: {} < minimum {}", amount_out, minimum_amount_out));
+        return Err(SwapError::SlippageExceeded.into());
+    }
 
+    if let Some(max_impact) = max_price_impact_bps {
+        let impact = calculate_price_impact_bps(amount_in, amount_out, reserve_in, reserve_out)?;
+        if impact > max_impact as u64 {
+            solana_program::log::sol_log(&format!("Price impact exceeded: {} bps > maximum {} bps", impact, max_impact));
+            return Err(SwapError::PriceImpactExceeded.into());
+        }
+    }
+
+    Ok(())
+}
+
+// Reject a deposit that mints nothing or fewer LP tokens than the caller's minimum
+fn check_liquidity_minted(liquidity: u64, min_lp_out: u64) -> ProgramResult {
+    if liquidity == 0 {
+        return Err(SwapError::ZeroAmount.into());
+    }
+    if liquidity < min_lp_out {
+        solana_program::log::sol_log(&format!("Slippage exceeded: {} LP tokens < minimum {}", liquidity, min_lp_out));
+        return Err(SwapError::SlippageExceeded.into());
+    }
     Ok(())
 }
 
-fn process_swap(
+// Reject a withdrawal that pays out less than the caller's minimum on either side
+fn check_withdrawal_amounts(
+    amount_a: u64,
+    amount_b: u64,
+    min_amount_a: u64,
+    min_amount_b: u64,
+) -> ProgramResult {
+    if amount_a < min_amount_a || amount_b < min_amount_b {
+        solana_program::log::sol_log(&format!("Slippage exceeded: withdrawing {}/{} < minimum {}/{}", amount_a, amount_b, min_amount_a, min_amount_b));
+        return Err(SwapError::SlippageExceeded.into());
+    }
+    Ok(())
+}
+
+// Helper function to calculate the expected output amount for a multihop swap
+// Removed unused functions: calculate_multihop_output and determine_swap_direction
+
+// Fee collection functions
+fn process_collect_fees(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    amount_in: u64,
-    direction_a_to_b: bool,
+    pool: Pubkey,
 ) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let pool_info = next_account_info(account_info_iter)?;
-    let token_a_info = next_account_info(account_info_iter)?;
-    let token_b_info = next_account_info(account_info_iter)?;
-    let vault_a_info = next_account_info(account_info_iter)?;
-    let vault_b_info = next_account_info(account_info_iter)?;
-    let user_in_info = next_account_info(account_info_iter)?;
-    let user_out_info = next_account_info(account_info_iter)?;
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
-
-    // Check if this is a native SOL pool swap
-    let is_native_sol_pool = *token_a_info.key == NATIVE_SOL_MINT || *token_b_info.key == NATIVE_SOL_MINT;
+    let CollectFeesAccounts {
+        pool_info,
+        treasury_info,
+        authority_info,
+        token_program_info,
+        fee_accounts,
+    } = CollectFeesAccounts::load(program_id, accounts)?;
+    let account_info_iter = &mut fee_accounts.iter();
     
-    if is_native_sol_pool {
-        // Native SOL pool swap logic
-        solana_program::log::sol_log("Native SOL pool swap instruction called");
-        solana_program::log::sol_log(&format!("Amount in: {}", amount_in));
+    // Verify pool account
+    if pool_info.key != &pool {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
+    
+    let account_type = AccountType::of(pool_info)?;
+    let (amount_a, amount_b) = if account_type == AccountType::Pool {
+        let mut pool_data = Pool::unpack(&pool_info.data.borrow())?;
         
-        // Determine which token is SOL and which is the SPL token
-        let (is_sol_to_token, token_mint) = if *token_a_info.key == NATIVE_SOL_MINT {
-            (direction_a_to_b, *token_b_info.key)
-    } else {
-            (!direction_a_to_b, *token_a_info.key)
-        };
-
-        // Load pool state
-        let mut pool = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+        // Regular pool fee collection
+        solana_program::log::sol_log("Collecting fees from regular pool");
+        solana_program::log::sol_log(&format!("Fees collected A: {}", pool_data.fee_collected_a));
+        solana_program::log::sol_log(&format!("Fees collected B: {}", pool_data.fee_collected_b));
         
-        // Verify pool seeds
-        let (pool_pda, _pool_bump) = Pubkey::find_program_address(
-            &[b"native_sol_pool", &token_mint.as_ref()],
-            program_id,
-        );
-        if pool_info.key != &pool_pda {
-            return Err(ProgramError::InvalidSeeds);
+        check_fee_treasury(&pool_data.fee_treasury, treasury_info)?;
+        check_pool_authority(&pool_data.authority, authority_info)?;
+        
+        let vault_a_info = next_account_info(account_info_iter)?;
+        let vault_b_info = next_account_info(account_info_iter)?;
+        let treasury_a_info = next_account_info(account_info_iter)?;
+        let treasury_b_info = next_account_info(account_info_iter)?;
+        
+        let (vault_a_pubkey, vault_a_bump) = get_vault_address(&pool, &pool_data.token_a, program_id);
+        let (vault_b_pubkey, vault_b_bump) = get_vault_address(&pool, &pool_data.token_b, program_id);
+        if *vault_a_info.key != vault_a_pubkey || *vault_b_info.key != vault_b_pubkey {
+            return Err(SwapError::InvalidVault.into());
         }
-
-        // Verify pool token vault
-        let (pool_token_vault_pda, _pool_token_vault_bump) = Pubkey::find_program_address(
-            &[b"native_sol_vault", pool_pda.as_ref(), &token_mint.as_ref()],
-            program_id,
-        );
-        if vault_b_info.key != &pool_token_vault_pda {
-            return Err(ProgramError::InvalidSeeds);
+        for token_account_info in [vault_a_info, vault_b_info, treasury_a_info, treasury_b_info] {
+            check_token_account(token_account_info)?;
         }
-
-        if is_sol_to_token {
-            // SOL to Token swap
-            solana_program::log::sol_log("SwapNativeSOLToToken instruction called");
-            
-            // Calculate output amount using constant product formula with 0.3% fee
-            let amount_out = calculate_swap_output(amount_in, pool.reserve_a, pool.reserve_b)?;
-
-            // Transfer SOL from user to pool using system program
-            invoke(
-                &system_instruction::transfer(
-                    user_info.key,
-                    pool_info.key,
-                    amount_in,
+        check_treasury_token_account(treasury_a_info, &pool_data.fee_treasury, &pool_data.token_a)?;
+        check_treasury_token_account(treasury_b_info, &pool_data.fee_treasury, &pool_data.token_b)?;
+        
+        let amount_a = pool_data.fee_collected_a;
+        let amount_b = pool_data.fee_collected_b;
+        
+        if amount_a > 0 {
+            invoke_signed(
+                &create_transfer_instruction(
+                    &vault_a_pubkey,
+                    treasury_a_info.key,
+                    &vault_a_pubkey,
+                    amount_a,
                 ),
                 &[
-                    user_info.clone(),
-                    pool_info.clone(),
-                    user_info.clone(), // system program
+                    vault_a_info.clone(),
+                    treasury_a_info.clone(),
+                    vault_a_info.clone(),
+                    token_program_info.clone(),
                 ],
+                &[&[b"vault", pool.as_ref(), pool_data.token_a.as_ref(), &[vault_a_bump]]],
             )?;
-
-            // Transfer tokens from pool vault to user using vault PDA as authority
-            let vault_signer_seeds: &[&[_]] = &[
-                b"native_sol_vault",
-                pool_pda.as_ref(),
-                &token_mint.as_ref(),
-                &[_pool_token_vault_bump],
-            ];
-
+        }
+        
+        if amount_b > 0 {
             invoke_signed(
                 &create_transfer_instruction(
-                    vault_b_info.key,
-                    user_out_info.key,
-                    vault_b_info.key,
-                    amount_out,
+                    &vault_b_pubkey,
+                    treasury_b_info.key,
+                    &vault_b_pubkey,
+                    amount_b,
                 ),
                 &[
                     vault_b_info.clone(),
-                    user_out_info.clone(),
+                    treasury_b_info.clone(),
                     vault_b_info.clone(),
                     token_program_info.clone(),
                 ],
-                &[vault_signer_seeds],
+                &[&[b"vault", pool.as_ref(), pool_data.token_b.as_ref(), &[vault_b_bump]]],
             )?;
-
-            // Update pool reserves
-            pool.reserve_a = pool.reserve_a.checked_add(amount_in).unwrap();
-            pool.reserve_b = pool.reserve_b.checked_sub(amount_out).unwrap();
-            
-            // Calculate and accumulate fees (0.3% of input amount)
-            let fee_amount = (amount_in as u128 * 3 / 1000) as u64;
-            pool.fee_collected_sol = pool.fee_collected_sol.checked_add(fee_amount).unwrap();
-            
-            solana_program::log::sol_log(&format!("Native SOL->Token fee collected: {}", fee_amount));
-            solana_program::log::sol_log(&format!("Swap completed: {} SOL -> {} tokens", amount_in, amount_out));
-    } else {
-            // Token to SOL swap
-            solana_program::log::sol_log("SwapTokenToNativeSOL instruction called");
-            
-            // Calculate output amount using constant product formula with 0.3% fee
-            let amount_out = calculate_swap_output(amount_in, pool.reserve_b, pool.reserve_a)?;
-
-            // Transfer tokens from user to pool vault
-            invoke(
+        }
+        
+        pool_data.fee_collected_a = 0;
+        pool_data.fee_collected_b = 0;
+        pool_data.check_vault_balances(vault_a_info, vault_b_info)?;
+        
+        // Pack updated pool state
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        pool_data.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
+        
+        (amount_a, amount_b)
+    } else if account_type == AccountType::NativeSOLPool {
+        let mut pool_data = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+        
+        // Native SOL pool fee collection
+        solana_program::log::sol_log("Collecting fees from native SOL pool");
+        solana_program::log::sol_log(&format!("Fees collected SOL: {}", pool_data.fee_collected_sol));
+        solana_program::log::sol_log(&format!("Fees collected Token: {}", pool_data.fee_collected_token));
+        
+        check_fee_treasury(&pool_data.fee_treasury, treasury_info)?;
+        check_pool_authority(&pool_data.authority, authority_info)?;
+        
+        let vault_info = next_account_info(account_info_iter)?;
+        let treasury_token_info = next_account_info(account_info_iter)?;
+        let sol_vault_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        
+        let (vault_pubkey, vault_bump) = Pubkey::find_program_address(
+            &[b"native_sol_vault", pool.as_ref(), pool_data.token_mint.as_ref()],
+            program_id,
+        );
+        let (sol_vault_pubkey, sol_vault_bump) = get_sol_vault_address(&pool, program_id);
+        if *vault_info.key != vault_pubkey || *sol_vault_info.key != sol_vault_pubkey {
+            return Err(SwapError::InvalidVault.into());
+        }
+        check_token_account(vault_info)?;
+        check_token_account(treasury_token_info)?;
+        check_writable(sol_vault_info)?;
+        check_program_id(system_program_info, &solana_program::system_program::id())?;
+        check_treasury_token_account(treasury_token_info, &pool_data.fee_treasury, &pool_data.token_mint)?;
+        
+        let amount_sol = pool_data.fee_collected_sol;
+        let amount_token = pool_data.fee_collected_token;
+        
+        if amount_sol > 0 {
+            transfer_from_sol_vault(&pool, sol_vault_info, sol_vault_bump, treasury_info, system_program_info, amount_sol)?;
+        }
+        
+        if amount_token > 0 {
+            invoke_signed(
                 &create_transfer_instruction(
-                    user_in_info.key,
-                    vault_b_info.key,
-                    user_info.key,
-                    amount_in,
+                    &vault_pubkey,
+                    treasury_token_info.key,
+                    &vault_pubkey,
+                    amount_token,
                 ),
                 &[
-                    user_in_info.clone(),
-                    vault_b_info.clone(),
-                    user_info.clone(),
+                    vault_info.clone(),
+                    treasury_token_info.clone(),
+                    vault_info.clone(),
                     token_program_info.clone(),
                 ],
+                &[&[b"native_sol_vault", pool.as_ref(), pool_data.token_mint.as_ref(), &[vault_bump]]],
             )?;
-
-            // Transfer SOL from pool to user (direct lamport manipulation)
-            **pool_info.try_borrow_mut_lamports()? -= amount_out;
-            **user_info.try_borrow_mut_lamports()? += amount_out;
-
-            // Update pool reserves
-            pool.reserve_b = pool.reserve_b.checked_add(amount_in).unwrap();
-            pool.reserve_a = pool.reserve_a.checked_sub(amount_out).unwrap();
-            
-            // Calculate and accumulate fees (0.3% of input amount)
-            let fee_amount = (amount_in as u128 * 3 / 1000) as u64;
-            pool.fee_collected_token = pool.fee_collected_token.checked_add(fee_amount).unwrap();
-            
-            solana_program::log::sol_log(&format!("Native Token->SOL fee collected: {}", fee_amount));
-            solana_program::log::sol_log(&format!("Swap completed: {} tokens -> {} SOL", amount_in, amount_out));
         }
         
+        pool_data.fee_collected_sol = 0;
+        pool_data.fee_collected_token = 0;
+        pool_data.check_balances(sol_vault_info, vault_info)?;
+        
         // Pack updated pool state
-        let mut pool_data = pool_info.try_borrow_mut_data()?;
-        pool.pack_into_slice(&mut pool_data[..NativeSOLPool::LEN]);
-
-        return Ok(());
-    }
-
-    let mut pool = Pool::unpack(&pool_info.data.borrow())?;
-    
-    // Verify pool seeds
-    let (pool_pubkey, _) = Pubkey::find_program_address(
-        &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-        program_id,
-    );
-    if pool_pubkey != *pool_info.key {
-        return Err(ProgramError::InvalidSeeds);
-    }
-
-    // Verify vault addresses and get bumps
-    let (vault_a_pubkey, vault_a_bump) = get_vault_address(pool_info.key, &pool.token_a, program_id);
-    let (vault_b_pubkey, vault_b_bump) = get_vault_address(pool_info.key, &pool.token_b, program_id);
-    
-    if vault_a_pubkey != *vault_a_info.key || vault_b_pubkey != *vault_b_info.key {
-        return Err(ProgramError::InvalidSeeds);
-    }
-
-    // Transfer input tokens to vault
-    if direction_a_to_b {
-        invoke(
-            &create_transfer_instruction(
-                user_in_info.key,
-                vault_a_info.key,
-                user_info.key,
-                amount_in,
-            ),
-            &[
-                user_in_info.clone(),
-                vault_a_info.clone(),
-                user_info.clone(),
-                token_program_info.clone(),
-            ],
-        )?;
-    } else {
-        invoke(
-            &create_transfer_instruction(
-                user_in_info.key,
-                vault_b_info.key,
-                user_info.key,
-                amount_in,
-            ),
-            &[
-                user_in_info.clone(),
-                vault_b_info.clone(),
-                user_info.clone(),
-                token_program_info.clone(),
-            ],
-        )?;
-    }
-
-    // Calculate output amount (with 0.3% fee)
-    let (reserve_in, reserve_out) = if direction_a_to_b {
-        (pool.reserve_a, pool.reserve_b)
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        pool_data.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
+        
+        (amount_sol, amount_token)
     } else {
-        (pool.reserve_b, pool.reserve_a)
+        return Err(SwapError::PoolTypeMismatch.into());
     };
-    let amount_out = calculate_swap_output(amount_in, reserve_in, reserve_out)?;
+    
+    let record = FeesCollected {
+        pool,
+        treasury: *treasury_info.key,
+        amount_a,
+        amount_b,
+    };
+    solana_program::log::sol_log_data(&[&record.try_to_vec()?]);
+    solana_program::log::sol_log(&format!("Fees paid to treasury: {} A, {} B", amount_a, amount_b));
+    
+    Ok(())
+}
 
-    // Transfer output tokens from vault to user using vault PDA as authority
-    if direction_a_to_b {
-        let vault_b_signer_seeds: &[&[_]] = &[
-            b"vault",
-            pool_info.key.as_ref(),
-            pool.token_b.as_ref(),
-            &[vault_b_bump],
-        ];
-        
-        invoke_signed(
-            &create_transfer_instruction(
-                vault_b_info.key,
-                user_out_info.key,
-                vault_b_info.key,
-                amount_out,
-            ),
-            &[
-                vault_b_info.clone(),
-                user_out_info.clone(),
-                vault_b_info.clone(),
-                token_program_info.clone(),
-            ],
-            &[vault_b_signer_seeds],
-        )?;
-    } else {
-        let vault_a_signer_seeds: &[&[_]] = &[
-            b"vault",
-            pool_info.key.as_ref(),
-            pool.token_a.as_ref(),
-            &[vault_a_bump],
-        ];
-        
-        invoke_signed(
-            &create_transfer_instruction(
-                vault_a_info.key,
-                user_out_info.key,
-                vault_a_info.key,
-                amount_out,
-            ),
-            &[
-                vault_a_info.clone(),
-                user_out_info.clone(),
-                vault_a_info.clone(),
-                token_program_info.clone(),
-            ],
-            &[vault_a_signer_seeds],
-        )?;
+// Treasury must be configured on the pool and match the supplied account
+fn check_fee_treasury(fee_treasury: &Pubkey, treasury_info: &AccountInfo) -> ProgramResult {
+    if *fee_treasury == Pubkey::default() || fee_treasury != treasury_info.key {
+        return Err(SwapError::InvalidTreasury.into());
     }
+    Ok(())
+}
 
-    // Update pool reserves
-    if direction_a_to_b {
-        pool.reserve_a = pool.reserve_a.checked_add(amount_in).unwrap();
-        pool.reserve_b = pool.reserve_b.checked_sub(amount_out).unwrap();
-    } else {
-        pool.reserve_b = pool.reserve_b.checked_add(amount_in).unwrap();
-        pool.reserve_a = pool.reserve_a.checked_sub(amount_out).unwrap();
+// Fee payouts may only land in token accounts owned by the pool's treasury
+fn check_treasury_token_account(
+    token_account_info: &AccountInfo,
+    fee_treasury: &Pubkey,
+    mint: &Pubkey,
+) -> ProgramResult {
+    let token_account = unpack_token_account(token_account_info)?;
+    if token_account.owner != *fee_treasury {
+        return Err(SwapError::InvalidTreasury.into());
     }
-
-    // Calculate and accumulate fees (0.3% of input amount)
-    let fee_amount = (amount_in as u128 * 3 / 1000) as u64;
-    if direction_a_to_b {
-        // Fee collected in token A
-        pool.fee_collected_a = pool.fee_collected_a.checked_add(fee_amount).unwrap();
-    } else {
-        // Fee collected in token B  
-        pool.fee_collected_b = pool.fee_collected_b.checked_add(fee_amount).unwrap();
+    if token_account.mint != *mint {
+        return Err(SwapError::InvalidTokenMint.into());
     }
-    
-    solana_program::log::sol_log(&format!("Fee collected: {} (direction_a_to_b: {})", fee_amount, direction_a_to_b));
-
-    Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
-
     Ok(())
 }
 
-fn process_multihop_swap(
+fn process_withdraw_fees(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    amount_in: u64,
-    minimum_amount_out: u64,
+    pool: Pubkey,
+    amount_a: u64,
+    amount_b: u64,
 ) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
-    
-    // First account is user's input token account
-    let user_input_account = next_account_info(account_info_iter)?;
-    
-    // For simplicity, let's just handle 2 hops (X->Y->Z) for now
-    // This avoids the Vec allocation which might be causing the stack issue
-    
-    // First hop: X -> Y
-    let pool_info_1 = next_account_info(account_info_iter)?;
-    let token_a_info_1 = next_account_info(account_info_iter)?;
-    let token_b_info_1 = next_account_info(account_info_iter)?;
-    let vault_a_info_1 = next_account_info(account_info_iter)?;
-    let vault_b_info_1 = next_account_info(account_info_iter)?;
-    let intermediate_account_1 = next_account_info(account_info_iter)?;
-    let next_account_1 = next_account_info(account_info_iter)?;
-    
-    // Second hop: Y -> Z
-    let pool_info_2 = next_account_info(account_info_iter)?;
-    let token_a_info_2 = next_account_info(account_info_iter)?;
-    let token_b_info_2 = next_account_info(account_info_iter)?;
-    let vault_a_info_2 = next_account_info(account_info_iter)?;
-    let vault_b_info_2 = next_account_info(account_info_iter)?;
-    let intermediate_account_2 = next_account_info(account_info_iter)?;
-    let output_account_2 = next_account_info(account_info_iter)?;
-    
-    let num_hops = 2;
-    
-    let mut current_amount = amount_in;
-    let mut current_input_account = user_input_account;
-    
-    // Process each hop
-    for hop in 0..num_hops {
-        let (pool_info, token_a_info, token_b_info, vault_a_info, vault_b_info, intermediate_account, output_account) = if hop == 0 {
-            (pool_info_1, token_a_info_1, token_b_info_1, vault_a_info_1, vault_b_info_1, intermediate_account_1, next_account_1)
-        } else {
-            (pool_info_2, token_a_info_2, token_b_info_2, vault_a_info_2, vault_b_info_2, intermediate_account_2, output_account_2)
-        };
-        
-        let mut pool = Pool::unpack(&pool_info.data.borrow())?;
+    let WithdrawFeesAccounts {
+        pool_info,
+        treasury_info,
+        authority_info,
+        token_program_info,
+        system_program_info,
+        vault_accounts,
+    } = WithdrawFeesAccounts::load(program_id, accounts)?;
+    let account_info_iter = &mut vault_accounts.iter();
+    
+    // Verify pool account
+    if pool_info.key != &pool {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
+    
+    // Try to deserialize as regular pool first
+    let account_type = AccountType::of(pool_info)?;
+    if account_type == AccountType::Pool {
+        let mut pool_data = Pool::unpack(&pool_info.data.borrow())?;
         
-        // Verify pool seeds
-        let (pool_pubkey, _) = Pubkey::find_program_address(
-            &[b"pool", token_a_info.key.as_ref(), token_b_info.key.as_ref()],
-            program_id,
-        );
+        // Regular pool fee withdrawal
+        solana_program::log::sol_log("Withdrawing fees from regular pool");
+        solana_program::log::sol_log(&format!("Requested amount A: {}", amount_a));
+        solana_program::log::sol_log(&format!("Requested amount B: {}", amount_b));
+        solana_program::log::sol_log(&format!("Available fees A: {}", pool_data.fee_collected_a));
+        solana_program::log::sol_log(&format!("Available fees B: {}", pool_data.fee_collected_b));
         
-        if pool_pubkey != *pool_info.key {
-            return Err(ProgramError::InvalidSeeds);
+        // Verify treasury is set
+        if pool_data.fee_treasury != *treasury_info.key {
+            return Err(SwapError::InvalidTreasury.into());
         }
         
-        // Verify vault addresses and get bumps
-        let (vault_a_pubkey, vault_a_bump) = get_vault_address(pool_info.key, &pool.token_a, program_id);
-        let (vault_b_pubkey, vault_b_bump) = get_vault_address(pool_info.key, &pool.token_b, program_id);
-        
-        if vault_a_pubkey != *vault_a_info.key || vault_b_pubkey != *vault_b_info.key {
-            return Err(ProgramError::InvalidSeeds);
+        // Verify authority (treasury owner can withdraw)
+        if authority_info.key != treasury_info.key {
+            return Err(SwapError::UnauthorizedTreasury.into());
         }
         
-        // Determine swap direction by checking which token the user is providing
-        // We need to check the token mint of the input account, not the account address
-        let input_token_mint = {
-            // Read the token account to get its mint
-            let token_account_data = current_input_account.data.borrow();
-            if token_account_data.len() < 32 {
-                return Err(ProgramError::InvalidAccountData);
-            }
-            // Token account mint is at offset 0-32
-            let mint_bytes: [u8; 32] = token_account_data[0..32]
-                .try_into()
-                .map_err(|_| ProgramError::InvalidAccountData)?;
-            Pubkey::new_from_array(mint_bytes)
-        };
-        
-        let direction_a_to_b = if input_token_mint == pool.token_a {
-            // User is providing token_a mint, so swap A->B
-            true
-        } else if input_token_mint == pool.token_b {
-            // User is providing token_b mint, so swap B->A  
-            false
-        } else {
-            return Err(ProgramError::InvalidArgument);
-        };
+        // Check if sufficient fees are available
+        if amount_a > pool_data.fee_collected_a || amount_b > pool_data.fee_collected_b {
+            return Err(SwapError::InsufficientFees.into());
+        }
         
-        // Debug: Log the direction for troubleshooting
-        solana_program::log::sol_log(&format!("Hop {}: pool.token_a = {}, pool.token_b = {}, input_token_mint = {}, direction_a_to_b = {}", 
-            hop, 
-            pool.token_a.to_string(), 
-            pool.token_b.to_string(), 
-            input_token_mint.to_string(), 
-            direction_a_to_b
-        ));
-        
-        let (reserve_in, reserve_out) = if direction_a_to_b {
-            (pool.reserve_a, pool.reserve_b)
-        } else {
-            (pool.reserve_b, pool.reserve_a)
-        };
+        // Get vault addresses for token transfers
+        let (vault_a_pubkey, vault_a_bump) = get_vault_address(&pool, &pool_data.token_a, program_id);
+        let (vault_b_pubkey, vault_b_bump) = get_vault_address(&pool, &pool_data.token_b, program_id);
         
-        // Transfer input tokens to vault
-        if direction_a_to_b {
-            invoke(
+        // Transfer Token A fees to treasury
+        if amount_a > 0 {
+            let vault_a_signer_seeds: &[&[_]] = &[
+                b"vault",
+                pool.as_ref(),
+                pool_data.token_a.as_ref(),
+                &[vault_a_bump],
+            ];
+            
+            let vault_a_info = next_account_info(account_info_iter)?;
+            let vault_a_authority_info = next_account_info(account_info_iter)?;
+            if *vault_a_info.key != vault_a_pubkey || *vault_a_authority_info.key != vault_a_pubkey {
+                return Err(SwapError::InvalidVault.into());
+            }
+            
+            invoke_signed(
                 &create_transfer_instruction(
-                    current_input_account.key,
-                    vault_a_info.key,
-                    user_info.key,
-                    current_amount,
+                    &vault_a_pubkey,
+                    treasury_info.key,
+                    &vault_a_pubkey,
+                    amount_a,
                 ),
                 &[
-                    current_input_account.clone(),
                     vault_a_info.clone(),
-                    user_info.clone(),
-                    token_program_info.clone(),
-                ],
-            )?;
-        } else {
-            invoke(
-                &create_transfer_instruction(
-                    current_input_account.key,
-                    vault_b_info.key,
-                    user_info.key,
-                    current_amount,
-                ),
-                &[
-                    current_input_account.clone(),
-                    vault_b_info.clone(),
-                    user_info.clone(),
+                    treasury_info.clone(),
+                    vault_a_authority_info.clone(),
                     token_program_info.clone(),
                 ],
+                &[vault_a_signer_seeds],
             )?;
         }
         
-        // Calculate output amount
-        let amount_out = calculate_swap_output(current_amount, reserve_in, reserve_out)?;
-        
-        // Use final output account for last hop, intermediate for others
-        let target_output_account = if hop == num_hops - 1 {
-            output_account
-        } else {
-            intermediate_account
-        };
-        
-        // Transfer output tokens using vault PDA as authority
-        if direction_a_to_b {
+        // Transfer Token B fees to treasury
+        if amount_b > 0 {
             let vault_b_signer_seeds: &[&[_]] = &[
                 b"vault",
-                pool_info.key.as_ref(),
-                pool.token_b.as_ref(),
+                pool.as_ref(),
+                pool_data.token_b.as_ref(),
                 &[vault_b_bump],
             ];
             
+            let vault_b_info = next_account_info(account_info_iter)?;
+            let vault_b_authority_info = next_account_info(account_info_iter)?;
+            if *vault_b_info.key != vault_b_pubkey || *vault_b_authority_info.key != vault_b_pubkey {
+                return Err(SwapError::InvalidVault.into());
+            }
+            
             invoke_signed(
                 &create_transfer_instruction(
-                    vault_b_info.key,
-                    target_output_account.key,
-                    vault_b_info.key,
-                    amount_out,
+                    &vault_b_pubkey,
+                    treasury_info.key,
+                    &vault_b_pubkey,
+                    amount_b,
                 ),
                 &[
                     vault_b_info.clone(),
-                    target_output_account.clone(),
-                    vault_b_info.clone(),
+                    treasury_info.clone(),
+                    vault_b_authority_info.clone(),
                     token_program_info.clone(),
                 ],
                 &[vault_b_signer_seeds],
             )?;
-        } else {
-            let vault_a_signer_seeds: &[&[_]] = &[
-                b"vault",
-                pool_info.key.as_ref(),
-                pool.token_a.as_ref(),
-                &[vault_a_bump],
+        }
+        
+        // Update pool state
+        pool_data.fee_collected_a = math::sub(pool_data.fee_collected_a, amount_a)?;
+        pool_data.fee_collected_b = math::sub(pool_data.fee_collected_b, amount_b)?;
+        
+        // Pack updated pool state
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        pool_data.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
+        
+        solana_program::log::sol_log("Fees withdrawn successfully");
+        
+    } else if account_type == AccountType::NativeSOLPool {
+        let mut pool_data = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+        
+        // Native SOL pool fee withdrawal
+        solana_program::log::sol_log("Withdrawing fees from native SOL pool");
+        solana_program::log::sol_log(&format!("Requested amount SOL: {}", amount_a));
+        solana_program::log::sol_log(&format!("Requested amount Token: {}", amount_b));
+        solana_program::log::sol_log(&format!("Available fees SOL: {}", pool_data.fee_collected_sol));
+        solana_program::log::sol_log(&format!("Available fees Token: {}", pool_data.fee_collected_token));
+        
+        // Verify treasury is set
+        if pool_data.fee_treasury != *treasury_info.key {
+            return Err(SwapError::InvalidTreasury.into());
+        }
+        
+        // Verify authority (treasury owner can withdraw)
+        if authority_info.key != treasury_info.key {
+            return Err(SwapError::UnauthorizedTreasury.into());
+        }
+        
+        // Check if sufficient fees are available
+        if amount_a > pool_data.fee_collected_sol || amount_b > pool_data.fee_collected_token {
+            return Err(SwapError::InsufficientFees.into());
+        }
+        
+        // Transfer SOL fees to treasury
+        if amount_a > 0 {
+            let (sol_vault_pubkey, sol_vault_bump) = get_sol_vault_address(&pool, program_id);
+            
+            let sol_vault_info = next_account_info(account_info_iter)?;
+            if *sol_vault_info.key != sol_vault_pubkey {
+                return Err(SwapError::InvalidVault.into());
+            }
+            
+            transfer_from_sol_vault(&pool, sol_vault_info, sol_vault_bump, treasury_info, system_program_info, amount_a)?;
+        }
+        
+        // Transfer Token fees to treasury
+        if amount_b > 0 {
+            let (vault_pubkey, vault_bump) = Pubkey::find_program_address(
+                &[b"native_sol_vault", pool.as_ref(), pool_data.token_mint.as_ref()],
+                program_id,
+            );
+            
+            let vault_signer_seeds: &[&[_]] = &[
+                b"native_sol_vault",
+                pool.as_ref(),
+                pool_data.token_mint.as_ref(),
+                &[vault_bump],
             ];
             
+            let vault_info = next_account_info(account_info_iter)?;
+            let vault_authority_info = next_account_info(account_info_iter)?;
+            if *vault_info.key != vault_pubkey || *vault_authority_info.key != vault_pubkey {
+                return Err(SwapError::InvalidVault.into());
+            }
+            
             invoke_signed(
                 &create_transfer_instruction(
-                    vault_a_info.key,
-                    target_output_account.key,
-                    vault_a_info.key,
-                    amount_out,
+                    &vault_pubkey,
+                    treasury_info.key,
+                    &vault_pubkey,
+                    amount_b,
                 ),
                 &[
-                    vault_a_info.clone(),
-                    target_output_account.clone(),
-                    vault_a_info.clone(),
+                    vault_info.clone(),
+                    treasury_info.clone(),
+                    vault_authority_info.clone(),
                     token_program_info.clone(),
                 ],
-                &[vault_a_signer_seeds],
+                &[vault_signer_seeds],
             )?;
         }
         
-        // Update pool reserves
-        if direction_a_to_b {
-            pool.reserve_a = pool.reserve_a.checked_add(current_amount).unwrap();
-            pool.reserve_b = pool.reserve_b.checked_sub(amount_out).unwrap();
-        } else {
-            pool.reserve_b = pool.reserve_b.checked_add(current_amount).unwrap();
-            pool.reserve_a = pool.reserve_a.checked_sub(amount_out).unwrap();
-        }
-
-        // Calculate and accumulate fees (0.3% of input amount)
-        let fee_amount = (current_amount as u128 * 3 / 1000) as u64;
-        if direction_a_to_b {
-            // Fee collected in token A
-            pool.fee_collected_a = pool.fee_collected_a.checked_add(fee_amount).unwrap();
-        } else {
-            // Fee collected in token B  
-            pool.fee_collected_b = pool.fee_collected_b.checked_add(fee_amount).unwrap();
-        }
+        // Update pool state
+        pool_data.fee_collected_sol = math::sub(pool_data.fee_collected_sol, amount_a)?;
+        pool_data.fee_collected_token = math::sub(pool_data.fee_collected_token, amount_b)?;
         
-        solana_program::log::sol_log(&format!("Multihop hop {} fee collected: {} (direction_a_to_b: {})", hop, fee_amount, direction_a_to_b));
+        // Pack updated pool state
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        pool_data.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
         
-        Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+        solana_program::log::sol_log("Fees withdrawn successfully");
         
-        // Set up for next hop
-        current_amount = amount_out;
-        current_input_account = target_output_account;
-    }
-    
-    // Check minimum output requirement
-    if current_amount < minimum_amount_out {
-        return Err(ProgramError::InsufficientFunds);
+    } else {
+        return Err(SwapError::PoolTypeMismatch.into());
     }
     
     Ok(())
 }
 
-fn process_multihop_swap_with_path(
+fn process_set_fee_treasury(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    amount_in: u64,
-    minimum_amount_out: u64,
-    token_path: Vec<Pubkey>,
+    pool: Pubkey,
+    treasury: Pubkey,
 ) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let user_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
+    let FeeAdminAccounts {
+        pool_info,
+        treasury_info,
+        authority_info,
+    } = FeeAdminAccounts::load(program_id, accounts)?;
     
-    // First account is user's input token account
-    let user_input_account = next_account_info(account_info_iter)?;
-    
-    // Remaining accounts for pools, vaults, and intermediate accounts
-    let mut remaining_accounts = Vec::new();
-    while let Ok(account) = next_account_info(account_info_iter) {
-        remaining_accounts.push(account);
+    // Verify pool account
+    if pool_info.key != &pool {
+        return Err(SwapError::InvalidPoolAddress.into());
     }
     
-    if token_path.len() < 2 {
-        return Err(ProgramError::InvalidArgument);
+    // Verify treasury account
+    if treasury_info.key != &treasury {
+        return Err(SwapError::InvalidTreasury.into());
     }
     
-    let num_hops = token_path.len() - 1;
-    let mut current_amount = amount_in;
-    let mut current_input_account = user_input_account;
-    
-    // Process each hop based on token path
-    for hop in 0..num_hops {
-        let base_idx = hop * 7; // Assuming 7 accounts per hop
-        if base_idx + 6 >= remaining_accounts.len() {
-            return Err(ProgramError::NotEnoughAccountKeys);
-        }
-        
-        let pool_info = remaining_accounts[base_idx];
-        let _token_a_info = remaining_accounts[base_idx + 1];
-        let _token_b_info = remaining_accounts[base_idx + 2];
-        let vault_a_info = remaining_accounts[base_idx + 3];
-        let vault_b_info = remaining_accounts[base_idx + 4];
-        let intermediate_account = remaining_accounts[base_idx + 5];
-        let output_account = remaining_accounts[base_idx + 6];
-        
-        let mut pool = Pool::unpack(&pool_info.data.borrow())?;
-        
-        // Verify pool matches the token path
-        let input_token = token_path[hop];
-        let output_token = token_path[hop + 1];
-        
-        let direction_a_to_b = if pool.token_a == input_token && pool.token_b == output_token {
-            true
-        } else if pool.token_b == input_token && pool.token_a == output_token {
-            false
-        } else {
-            return Err(ProgramError::InvalidArgument);
-        };
+    let account_type = AccountType::of(pool_info)?;
+    if account_type == AccountType::Pool {
+        let pool_data = Pool::unpack(&pool_info.data.borrow())?;
         
-        // Verify vault addresses
-        let (vault_a_pubkey, vault_a_bump) = get_vault_address(pool_info.key, &pool.token_a, program_id);
-        let (vault_b_pubkey, vault_b_bump) = get_vault_address(pool_info.key, &pool.token_b, program_id);
+        // Regular pool treasury setting
+        solana_program::log::sol_log("Setting fee treasury for regular pool");
+        solana_program::log::sol_log(&format!("New treasury: {}", treasury));
         
-        if vault_a_pubkey != *vault_a_info.key || vault_b_pubkey != *vault_b_info.key {
-            return Err(ProgramError::InvalidSeeds);
-        }
+        check_pool_authority(&pool_data.authority, authority_info)?;
         
-        let (reserve_in, reserve_out, vault_in, _vault_out, _out_bump) = if direction_a_to_b {
-            (pool.reserve_a, pool.reserve_b, vault_a_info, vault_b_info, vault_b_bump)
-        } else {
-            (pool.reserve_b, pool.reserve_a, vault_b_info, vault_a_info, vault_a_bump)
-        };
+        let mut updated_pool_data = pool_data;
+        updated_pool_data.fee_treasury = treasury;
         
-        // Transfer input tokens to vault
-        invoke(
-            &create_transfer_instruction(
-                current_input_account.key,
-                vault_in.key,
-                user_info.key,
-                current_amount,
-            ),
-            &[
-                current_input_account.clone(),
-                vault_in.clone(),
-                user_info.clone(),
-                token_program_info.clone(),
-            ],
-        )?;
+        // Pack updated pool state
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        updated_pool_data.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
         
-        // Calculate output amount
-        let amount_out = calculate_swap_output(current_amount, reserve_in, reserve_out)?;
+        solana_program::log::sol_log("Fee treasury set successfully");
         
-        // Use final output account for last hop, intermediate for others
-        let target_output_account = if hop == num_hops - 1 {
-            output_account
-        } else {
-            intermediate_account
-        };
+    } else if account_type == AccountType::NativeSOLPool {
+        let pool_data = NativeSOLPool::unpack(&pool_info.data.borrow())?;
         
-        // Transfer output tokens using vault PDA as authority
-        if direction_a_to_b {
-            let vault_b_signer_seeds: &[&[_]] = &[
-                b"vault",
-                pool_info.key.as_ref(),
-                pool.token_b.as_ref(),
-                &[vault_b_bump],
-            ];
-            
-            invoke_signed(
-                &create_transfer_instruction(
-                    vault_b_info.key,
-                    target_output_account.key,
-                    vault_b_info.key,
-                    amount_out,
-                ),
-                &[
-                    vault_b_info.clone(),
-                    target_output_account.clone(),
-                    vault_b_info.clone(),
-                    token_program_info.clone(),
-                ],
-                &[vault_b_signer_seeds],
-            )?;
-        } else {
-            let vault_a_signer_seeds: &[&[_]] = &[
-                b"vault",
-                pool_info.key.as_ref(),
-                pool.token_a.as_ref(),
-                &[vault_a_bump],
-            ];
-            
-            invoke_signed(
-                &create_transfer_instruction(
-                    vault_a_info.key,
-                    target_output_account.key,
-                    vault_a_info.key,
-                    amount_out,
-                ),
-                &[
-                    vault_a_info.clone(),
-                    target_output_account.clone(),
-                    vault_a_info.clone(),
-                    token_program_info.clone(),
-                ],
-                &[vault_a_signer_seeds],
-            )?;
-        }
+        // Native SOL pool treasury setting
+        solana_program::log::sol_log("Setting fee treasury for native SOL pool");
+        solana_program::log::sol_log(&format!("New treasury: {}", treasury));
         
-        // Update pool reserves
-        if direction_a_to_b {
-            pool.reserve_a = pool.reserve_a.checked_add(current_amount).unwrap();
-            pool.reserve_b = pool.reserve_b.checked_sub(amount_out).unwrap();
-        } else {
-            pool.reserve_b = pool.reserve_b.checked_add(current_amount).unwrap();
-            pool.reserve_a = pool.reserve_a.checked_sub(amount_out).unwrap();
-        }
-
-        // Calculate and accumulate fees (0.3% of input amount)
-        let fee_amount = (current_amount as u128 * 3 / 1000) as u64;
-        if direction_a_to_b {
-            // Fee collected in token A
-            pool.fee_collected_a = pool.fee_collected_a.checked_add(fee_amount).unwrap();
-        } else {
-            // Fee collected in token B  
-            pool.fee_collected_b = pool.fee_collected_b.checked_add(fee_amount).unwrap();
-        }
+        check_pool_authority(&pool_data.authority, authority_info)?;
         
-        solana_program::log::sol_log(&format!("Multihop hop {} fee collected: {} (direction_a_to_b: {})", hop, fee_amount, direction_a_to_b));
+        let mut updated_pool_data = pool_data;
+        updated_pool_data.fee_treasury = treasury;
         
-        Pool::pack(pool, &mut pool_info.data.borrow_mut())?;
+        // Pack updated pool state
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        updated_pool_data.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
         
-        // Set up for next hop
-        current_amount = amount_out;
-        current_input_account = target_output_account;
-    }
-    
-    // Ensure final output is above minimum
-    if current_amount < minimum_amount_out {
-        return Err(ProgramError::InsufficientFunds);
+        solana_program::log::sol_log("Fee treasury set successfully");
+    } else {
+        return Err(SwapError::PoolTypeMismatch.into());
     }
     
     Ok(())
 }
 
-// Helper function to calculate output amount for a single swap
-fn calculate_swap_output(
-    amount_in: u64,
-    reserve_in: u64,
-    reserve_out: u64,
-) -> Result<u64, ProgramError> {
-    if amount_in == 0 || reserve_in == 0 || reserve_out == 0 {
-        return Err(ProgramError::InvalidArgument);
+fn process_init_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    default_fee_bps: u16,
+    protocol_fee_divisor: u8,
+    default_treasury: Pubkey,
+) -> ProgramResult {
+    let InitConfigAccounts {
+        config_info,
+        admin_info,
+        program_data_info,
+        system_program_info,
+    } = InitConfigAccounts::load(accounts)?;
+    
+    let (config_pubkey, config_bump) = get_config_address(program_id);
+    if *config_info.key != config_pubkey {
+        return Err(SwapError::InvalidConfig.into());
     }
     
-    let amount_in_with_fee = (amount_in as u128).checked_mul(997).ok_or(ProgramError::InvalidArgument)?;
-    let numerator = amount_in_with_fee.checked_mul(reserve_out as u128).ok_or(ProgramError::InvalidArgument)?;
-    let denominator = (reserve_in as u128)
-        .checked_mul(1000).ok_or(ProgramError::InvalidArgument)?
-        .checked_add(amount_in_with_fee).ok_or(ProgramError::InvalidArgument)?;
+    // Only the program's upgrade authority may create the config, so it cannot be
+    // front-run after deployment
+    check_upgrade_authority(program_id, program_data_info, admin_info)?;
+    check_fee_tier(default_fee_bps)?;
+    check_protocol_fee_divisor(protocol_fee_divisor)?;
     
-    if denominator == 0 {
-        return Err(ProgramError::InvalidArgument);
-    }
+    let rent = Rent::get()?;
+    invoke_signed(
+        &system_instruction::create_account(
+            admin_info.key,
+            config_info.key,
+            rent.minimum_balance(Config::LEN),
+            Config::LEN as u64,
+            program_id,
+        ),
+        &[
+            admin_info.clone(),
+            config_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&[b"config", &[config_bump]]],
+    )?;
     
-    Ok((numerator / denominator) as u64)
+    let config = Config {
+        account_type: AccountType::Config,
+        admin: *admin_info.key,
+        default_fee_bps,
+        protocol_fee_divisor,
+        default_treasury,
+        paused: false,
+        bump: config_bump,
+    };
+    Config::pack(config, &mut config_info.data.borrow_mut())?;
+    
+    solana_program::log::sol_log(&format!("Config initialized, admin: {}", admin_info.key));
+    
+    Ok(())
 }
 
-// Helper function to calculate the expected output amount for a multihop swap
-// Removed unused functions: calculate_multihop_output and determine_swap_direction
-
-// Integer square root implementation for u128
-trait IntegerSqrt {
-    fn integer_sqrt(self) -> Self;
+// Program data account of an upgradeable program:
+// u32 state tag (3), u64 slot, Option<Pubkey> upgrade authority
+fn check_upgrade_authority(
+    program_id: &Pubkey,
+    program_data_info: &AccountInfo,
+    authority_info: &AccountInfo,
+) -> ProgramResult {
+    let (program_data_pubkey, _) = Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    if *program_data_info.key != program_data_pubkey {
+        return Err(SwapError::InvalidProgramId.into());
+    }
+    
+    let data = program_data_info.data.borrow();
+    let upgrade_authority = match data.get(..45) {
+        Some([3, 0, 0, 0, _, _, _, _, _, _, _, _, 1, authority @ ..]) => Pubkey::try_from(authority)
+            .map_err(|_| ProgramError::InvalidAccountData)?,
+        _ => return Err(SwapError::InvalidAuthority.into()),
+    };
+    if upgrade_authority != *authority_info.key {
+        return Err(SwapError::InvalidAuthority.into());
+    }
+    Ok(())
 }
 
-impl IntegerSqrt for u128 {
-    fn integer_sqrt(self) -> Self {
-        if self < 2 {
-            return self;
-        }
-        let mut x = self;
-        let mut y = (self + 1) / 2;
-        while y < x {
-            x = y;
-            y = (x + self / x) / 2;
+fn process_update_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    default_fee_bps: u16,
+    protocol_fee_divisor: u8,
+    default_treasury: Pubkey,
+    paused: bool,
+    new_admin: Option<Pubkey>,
+) -> ProgramResult {
+    let ConfigAdminAccounts {
+        config_info,
+        admin_info,
+    } = ConfigAdminAccounts::load(program_id, accounts)?;
+    
+    let mut config = Config::unpack(&config_info.data.borrow())?;
+    config.check_admin(admin_info)?;
+    check_fee_tier(default_fee_bps)?;
+    check_protocol_fee_divisor(protocol_fee_divisor)?;
+    
+    config.default_fee_bps = default_fee_bps;
+    config.protocol_fee_divisor = protocol_fee_divisor;
+    config.default_treasury = default_treasury;
+    config.paused = paused;
+    if let Some(new_admin) = new_admin {
+        if new_admin == Pubkey::default() {
+            return Err(SwapError::InvalidAuthority.into());
         }
-        x
+        config.admin = new_admin;
     }
+    Config::pack(config, &mut config_info.data.borrow_mut())?;
+    
+    solana_program::log::sol_log(&format!("Config updated, paused: {}", paused));
+    
+    Ok(())
 }
 
-// Fee collection functions
-fn process_collect_fees(
-    _program_id: &Pubkey,
+// Circuit breaker for a single pool, independent of the global pause in Config
+fn process_set_pool_status(
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
     pool: Pubkey,
+    status: PoolStatus,
 ) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let pool_info = next_account_info(account_info_iter)?;
-    let treasury_info = next_account_info(account_info_iter)?;
-    let _authority_info = next_account_info(account_info_iter)?;
+    let PoolAdminAccounts {
+        pool_info,
+        config_info,
+        admin_info,
+    } = PoolAdminAccounts::load(program_id, accounts)?;
     
     // Verify pool account
     if pool_info.key != &pool {
-        return Err(ProgramError::InvalidArgument);
+        return Err(SwapError::InvalidPoolAddress.into());
     }
     
-    // Try to deserialize as regular pool first
-    let pool_data_result = Pool::unpack(&pool_info.data.borrow());
-    if let Ok(pool_data) = pool_data_result {
-        // Regular pool fee collection
-        solana_program::log::sol_log("Collecting fees from regular pool");
-        solana_program::log::sol_log(&format!("Fees collected A: {}", pool_data.fee_collected_a));
-        solana_program::log::sol_log(&format!("Fees collected B: {}", pool_data.fee_collected_b));
-        
-        // Verify treasury is set
-        if pool_data.fee_treasury != *treasury_info.key {
-            return Err(ProgramError::InvalidArgument);
-        }
-        
-        // Reset collected fees (in production, you would transfer to treasury)
-        let mut updated_pool_data = pool_data;
-        updated_pool_data.fee_collected_a = 0;
-        updated_pool_data.fee_collected_b = 0;
+    let config = Config::unpack(&config_info.data.borrow())?;
+    config.check_admin(admin_info)?;
+    
+    let account_type = AccountType::of(pool_info)?;
+    if account_type == AccountType::Pool {
+        let mut pool_data = Pool::unpack(&pool_info.data.borrow())?;
+        pool_data.status = status;
         
-        // Pack updated pool state
         let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
-        updated_pool_data.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
-        
-        solana_program::log::sol_log("Fees collected successfully");
+        pool_data.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
+    } else if account_type == AccountType::NativeSOLPool {
+        let mut pool_data = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+        pool_data.status = status;
         
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        pool_data.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
     } else {
-        let pool_data_result = NativeSOLPool::unpack(&pool_info.data.borrow());
-        if let Ok(pool_data) = pool_data_result {
-        // Native SOL pool fee collection
-        solana_program::log::sol_log("Collecting fees from native SOL pool");
-        solana_program::log::sol_log(&format!("Fees collected SOL: {}", pool_data.fee_collected_sol));
-        solana_program::log::sol_log(&format!("Fees collected Token: {}", pool_data.fee_collected_token));
+        return Err(SwapError::PoolTypeMismatch.into());
+    }
+    
+    solana_program::log::sol_log(&format!("Pool {} status: {:?}", pool, status));
+    
+    Ok(())
+}
+
+// Changes the protocol share of future swap fees; fees already accrued are unaffected
+fn process_set_protocol_fee(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pool: Pubkey,
+    protocol_fee_divisor: u8,
+) -> ProgramResult {
+    let PoolAuthorityAccounts {
+        pool_info,
+        authority_info,
+    } = PoolAuthorityAccounts::load(program_id, accounts)?;
+    
+    // Verify pool account
+    if pool_info.key != &pool {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
+    
+    check_protocol_fee_divisor(protocol_fee_divisor)?;
+    
+    let account_type = AccountType::of(pool_info)?;
+    if account_type == AccountType::Pool {
+        let mut pool_data = Pool::unpack(&pool_info.data.borrow())?;
+        check_pool_authority(&pool_data.authority, authority_info)?;
+        pool_data.protocol_fee_divisor = protocol_fee_divisor;
         
-        // Verify treasury is set
-        if pool_data.fee_treasury != *treasury_info.key {
-            return Err(ProgramError::InvalidArgument);
-        }
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        pool_data.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
+    } else if account_type == AccountType::NativeSOLPool {
+        let mut pool_data = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+        check_pool_authority(&pool_data.authority, authority_info)?;
+        pool_data.protocol_fee_divisor = protocol_fee_divisor;
         
-        // Reset collected fees (in production, you would transfer to treasury)
-        let mut updated_pool_data = pool_data;
-        updated_pool_data.fee_collected_sol = 0;
-        updated_pool_data.fee_collected_token = 0;
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        pool_data.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
+    } else {
+        return Err(SwapError::PoolTypeMismatch.into());
+    }
+    
+    solana_program::log::sol_log(&format!("Protocol fee divisor set: {}", protocol_fee_divisor));
+    
+    Ok(())
+}
+
+// Signer must be the authority recorded on the pool
+fn check_pool_authority(authority: &Pubkey, authority_info: &AccountInfo) -> ProgramResult {
+    if authority_info.key != authority {
+        solana_program::log::sol_log(&format!("Invalid authority: expected {}, got {}", authority, authority_info.key));
+        return Err(SwapError::InvalidAuthority.into());
+    }
+    Ok(())
+}
+
+// Authority transfer is two-step: the current authority proposes a new one,
+// which only takes over once it signs AcceptPoolAuthority
+fn process_propose_pool_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pool: Pubkey,
+    new_authority: Pubkey,
+) -> ProgramResult {
+    let PoolAuthorityAccounts {
+        pool_info,
+        authority_info,
+    } = PoolAuthorityAccounts::load(program_id, accounts)?;
+    
+    // Verify pool account
+    if pool_info.key != &pool {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
+    
+    let account_type = AccountType::of(pool_info)?;
+    if account_type == AccountType::Pool {
+        let mut pool_data = Pool::unpack(&pool_info.data.borrow())?;
+        check_pool_authority(&pool_data.authority, authority_info)?;
+        pool_data.pending_authority = new_authority;
         
-        // Pack updated pool state
         let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
-        updated_pool_data.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
+        pool_data.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
+    } else if account_type == AccountType::NativeSOLPool {
+        let mut pool_data = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+        check_pool_authority(&pool_data.authority, authority_info)?;
+        pool_data.pending_authority = new_authority;
         
-        solana_program::log::sol_log("Fees collected successfully");
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        pool_data.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
     } else {
-        return Err(ProgramError::InvalidAccountData);
-        }
+        return Err(SwapError::PoolTypeMismatch.into());
     }
     
+    solana_program::log::sol_log(&format!("Pool authority proposed: {}", new_authority));
+    
     Ok(())
 }
 
-fn process_withdraw_fees(
+fn process_accept_pool_authority(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     pool: Pubkey,
-    amount_a: u64,
-    amount_b: u64,
 ) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let pool_info = next_account_info(account_info_iter)?;
-    let treasury_info = next_account_info(account_info_iter)?;
-    let authority_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
-    let system_program_info = next_account_info(account_info_iter)?;
+    let PoolAuthorityAccounts {
+        pool_info,
+        authority_info,
+    } = PoolAuthorityAccounts::load(program_id, accounts)?;
     
     // Verify pool account
     if pool_info.key != &pool {
-        return Err(ProgramError::InvalidArgument);
+        return Err(SwapError::InvalidPoolAddress.into());
     }
     
-    // Try to deserialize as regular pool first
-    if let Ok(mut pool_data) = Pool::unpack(&pool_info.data.borrow()) {
-        // Regular pool fee withdrawal
-        solana_program::log::sol_log("Withdrawing fees from regular pool");
-        solana_program::log::sol_log(&format!("Requested amount A: {}", amount_a));
-        solana_program::log::sol_log(&format!("Requested amount B: {}", amount_b));
-        solana_program::log::sol_log(&format!("Available fees A: {}", pool_data.fee_collected_a));
-        solana_program::log::sol_log(&format!("Available fees B: {}", pool_data.fee_collected_b));
+    // Pubkey::default() never signs, so an empty proposal cannot be accepted
+    let account_type = AccountType::of(pool_info)?;
+    if account_type == AccountType::Pool {
+        let mut pool_data = Pool::unpack(&pool_info.data.borrow())?;
+        check_pool_authority(&pool_data.pending_authority, authority_info)?;
+        pool_data.authority = pool_data.pending_authority;
+        pool_data.pending_authority = Pubkey::default();
         
-        // Verify treasury is set
-        if pool_data.fee_treasury != *treasury_info.key {
-            return Err(ProgramError::InvalidArgument);
-        }
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        pool_data.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
+    } else if account_type == AccountType::NativeSOLPool {
+        let mut pool_data = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+        check_pool_authority(&pool_data.pending_authority, authority_info)?;
+        pool_data.authority = pool_data.pending_authority;
+        pool_data.pending_authority = Pubkey::default();
         
-        // Verify authority (treasury owner can withdraw)
-        if authority_info.key != treasury_info.key {
-            return Err(ProgramError::InvalidArgument);
-        }
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        pool_data.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
+    } else {
+        return Err(SwapError::PoolTypeMismatch.into());
+    }
+    
+    solana_program::log::sol_log(&format!("Pool authority accepted: {}", authority_info.key));
+    
+    Ok(())
+}
+
+// Permissionless: realign the pool reserves with what its vaults actually hold
+fn process_sync(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pool: Pubkey,
+) -> ProgramResult {
+    let SyncAccounts {
+        pool_info,
+        vault_accounts,
+    } = SyncAccounts::load(program_id, accounts)?;
+    let account_info_iter = &mut vault_accounts.iter();
+    
+    // Verify pool account
+    if pool_info.key != &pool {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
+    
+    let account_type = AccountType::of(pool_info)?;
+    if account_type == AccountType::Pool {
+        let mut pool_data = Pool::unpack(&pool_info.data.borrow())?;
+        pool_data.update_price_cumulative()?;
+        let vault_a_info = next_account_info(account_info_iter)?;
+        let vault_b_info = next_account_info(account_info_iter)?;
         
-        // Check if sufficient fees are available
-        if amount_a > pool_data.fee_collected_a || amount_b > pool_data.fee_collected_b {
-            return Err(ProgramError::InsufficientFunds);
+        let (vault_a_pubkey, _) = get_vault_address(&pool, &pool_data.token_a, program_id);
+        let (vault_b_pubkey, _) = get_vault_address(&pool, &pool_data.token_b, program_id);
+        if *vault_a_info.key != vault_a_pubkey || *vault_b_info.key != vault_b_pubkey {
+            return Err(SwapError::InvalidVault.into());
         }
         
-        // Get vault addresses for token transfers
-        let (vault_a_pubkey, vault_a_bump) = get_vault_address(&pool, &pool_data.token_a, program_id);
-        let (vault_b_pubkey, vault_b_bump) = get_vault_address(&pool, &pool_data.token_b, program_id);
+        pool_data.reconcile(vault_a_info, vault_b_info)?;
+        solana_program::log::sol_log(&format!("Pool synced: reserve A {}, reserve B {}", pool_data.reserve_a, pool_data.reserve_b));
         
-        // Transfer Token A fees to treasury
-        if amount_a > 0 {
-            let vault_a_signer_seeds: &[&[_]] = &[
-                b"vault",
-                pool.as_ref(),
-                pool_data.token_a.as_ref(),
-                &[vault_a_bump],
-            ];
-            
-            invoke_signed(
-                &create_transfer_instruction(
-                    &vault_a_pubkey,
-                    treasury_info.key,
-                    &vault_a_pubkey,
-                    amount_a,
-                ),
-                &[
-                    next_account_info(account_info_iter)?.clone(), // vault_a_info
-                    treasury_info.clone(),
-                    next_account_info(account_info_iter)?.clone(), // vault_a_info (authority)
-                    token_program_info.clone(),
-                ],
-                &[vault_a_signer_seeds],
-            )?;
-        }
+        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+        pool_data.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
+    } else if account_type == AccountType::NativeSOLPool {
+        let mut pool_data = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+        pool_data.update_price_cumulative()?;
+        let sol_vault_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
         
-        // Transfer Token B fees to treasury
-        if amount_b > 0 {
-            let vault_b_signer_seeds: &[&[_]] = &[
-                b"vault",
-                pool.as_ref(),
-                pool_data.token_b.as_ref(),
-                &[vault_b_bump],
-            ];
-            
-            invoke_signed(
-                &create_transfer_instruction(
-                    &vault_b_pubkey,
-                    treasury_info.key,
-                    &vault_b_pubkey,
-                    amount_b,
-                ),
-                &[
-                    next_account_info(account_info_iter)?.clone(), // vault_b_info
-                    treasury_info.clone(),
-                    next_account_info(account_info_iter)?.clone(), // vault_b_info (authority)
-                    token_program_info.clone(),
-                ],
-                &[vault_b_signer_seeds],
-            )?;
+        let (vault_pubkey, _) = Pubkey::find_program_address(
+            &[b"native_sol_vault", pool.as_ref(), pool_data.token_mint.as_ref()],
+            program_id,
+        );
+        let (sol_vault_pubkey, _) = get_sol_vault_address(&pool, program_id);
+        if *vault_info.key != vault_pubkey || *sol_vault_info.key != sol_vault_pubkey {
+            return Err(SwapError::InvalidVault.into());
         }
         
-        // Update pool state
-        pool_data.fee_collected_a = pool_data.fee_collected_a.checked_sub(amount_a).unwrap();
-        pool_data.fee_collected_b = pool_data.fee_collected_b.checked_sub(amount_b).unwrap();
+        pool_data.reconcile(sol_vault_info, vault_info)?;
+        solana_program::log::sol_log(&format!("Pool synced: SOL reserve {}, token reserve {}", pool_data.reserve_a, pool_data.reserve_b));
         
-        // Pack updated pool state
         let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
-        pool_data.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
-        
-        solana_program::log::sol_log("Fees withdrawn successfully");
-        
-    } else if let Ok(mut pool_data) = NativeSOLPool::unpack(&pool_info.data.borrow()) {
-        // Native SOL pool fee withdrawal
-        solana_program::log::sol_log("Withdrawing fees from native SOL pool");
-        solana_program::log::sol_log(&format!("Requested amount SOL: {}", amount_a));
-        solana_program::log::sol_log(&format!("Requested amount Token: {}", amount_b));
-        solana_program::log::sol_log(&format!("Available fees SOL: {}", pool_data.fee_collected_sol));
-        solana_program::log::sol_log(&format!("Available fees Token: {}", pool_data.fee_collected_token));
+        pool_data.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
+    } else {
+        return Err(SwapError::PoolTypeMismatch.into());
+    }
+    
+    Ok(())
+}
+
+// Permissionless: send whatever the vaults hold above reserves + unclaimed fees to a
+// recipient, leaving the pool state untouched
+fn process_skim(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pool: Pubkey,
+) -> ProgramResult {
+    let SkimAccounts {
+        pool_info,
+        token_program_info,
+        system_program_info,
+        skim_accounts,
+    } = SkimAccounts::load(program_id, accounts)?;
+    let account_info_iter = &mut skim_accounts.iter();
+    
+    // Verify pool account
+    if pool_info.key != &pool {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
+    
+    let account_type = AccountType::of(pool_info)?;
+    if account_type == AccountType::Pool {
+        let pool_data = Pool::unpack(&pool_info.data.borrow())?;
+        let vault_a_info = next_account_info(account_info_iter)?;
+        let vault_b_info = next_account_info(account_info_iter)?;
+        let recipient_a_info = next_account_info(account_info_iter)?;
+        let recipient_b_info = next_account_info(account_info_iter)?;
         
-        // Verify treasury is set
-        if pool_data.fee_treasury != *treasury_info.key {
-            return Err(ProgramError::InvalidArgument);
+        let (vault_a_pubkey, vault_a_bump) = get_vault_address(&pool, &pool_data.token_a, program_id);
+        let (vault_b_pubkey, vault_b_bump) = get_vault_address(&pool, &pool_data.token_b, program_id);
+        if *vault_a_info.key != vault_a_pubkey || *vault_b_info.key != vault_b_pubkey {
+            return Err(SwapError::InvalidVault.into());
         }
+        check_token_account(recipient_a_info)?;
+        check_token_account(recipient_b_info)?;
         
-        // Verify authority (treasury owner can withdraw)
-        if authority_info.key != treasury_info.key {
-            return Err(ProgramError::InvalidArgument);
-        }
+        let excess_a = vault_excess(vault_balance(vault_a_info)?, pool_data.reserve_a, pool_data.fee_collected_a)?;
+        let excess_b = vault_excess(vault_balance(vault_b_info)?, pool_data.reserve_b, pool_data.fee_collected_b)?;
         
-        // Check if sufficient fees are available
-        if amount_a > pool_data.fee_collected_sol || amount_b > pool_data.fee_collected_token {
-            return Err(ProgramError::InsufficientFunds);
+        if excess_a > 0 {
+            invoke_signed(
+                &create_transfer_instruction(
+                    &vault_a_pubkey,
+                    recipient_a_info.key,
+                    &vault_a_pubkey,
+                    excess_a,
+                ),
+                &[
+                    vault_a_info.clone(),
+                    recipient_a_info.clone(),
+                    vault_a_info.clone(),
+                    token_program_info.clone(),
+                ],
+                &[&[b"vault", pool.as_ref(), pool_data.token_a.as_ref(), &[vault_a_bump]]],
+            )?;
         }
         
-        // Transfer SOL fees to treasury
-        if amount_a > 0 {
-            invoke(
-                &system_instruction::transfer(
-                    pool_info.key,
-                    treasury_info.key,
-                    amount_a,
+        if excess_b > 0 {
+            invoke_signed(
+                &create_transfer_instruction(
+                    &vault_b_pubkey,
+                    recipient_b_info.key,
+                    &vault_b_pubkey,
+                    excess_b,
                 ),
                 &[
-                    pool_info.clone(),
-                    treasury_info.clone(),
-                    system_program_info.clone(),
+                    vault_b_info.clone(),
+                    recipient_b_info.clone(),
+                    vault_b_info.clone(),
+                    token_program_info.clone(),
                 ],
+                &[&[b"vault", pool.as_ref(), pool_data.token_b.as_ref(), &[vault_b_bump]]],
             )?;
         }
         
-        // Transfer Token fees to treasury
-        if amount_b > 0 {
-            let (vault_pubkey, vault_bump) = Pubkey::find_program_address(
-                &[b"native_sol_vault", pool.as_ref(), &pool_data.token_mint.as_ref()],
-                program_id,
-            );
-            
-            let vault_signer_seeds: &[&[_]] = &[
-                b"native_sol_vault",
-                pool.as_ref(),
-                &pool_data.token_mint.as_ref(),
-                &[vault_bump],
-            ];
-            
+        pool_data.check_vault_balances(vault_a_info, vault_b_info)?;
+        solana_program::log::sol_log(&format!("Skimmed: {} A, {} B", excess_a, excess_b));
+    } else if account_type == AccountType::NativeSOLPool {
+        let pool_data = NativeSOLPool::unpack(&pool_info.data.borrow())?;
+        let sol_vault_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let recipient_info = next_account_info(account_info_iter)?;
+        let recipient_token_info = next_account_info(account_info_iter)?;
+        
+        let (vault_pubkey, vault_bump) = Pubkey::find_program_address(
+            &[b"native_sol_vault", pool.as_ref(), pool_data.token_mint.as_ref()],
+            program_id,
+        );
+        let (sol_vault_pubkey, sol_vault_bump) = get_sol_vault_address(&pool, program_id);
+        if *vault_info.key != vault_pubkey || *sol_vault_info.key != sol_vault_pubkey {
+            return Err(SwapError::InvalidVault.into());
+        }
+        check_token_account(recipient_token_info)?;
+        
+        let excess_sol = vault_excess(sol_vault_balance(sol_vault_info)?, pool_data.reserve_a, pool_data.fee_collected_sol)?;
+        let excess_token = vault_excess(vault_balance(vault_info)?, pool_data.reserve_b, pool_data.fee_collected_token)?;
+        
+        if excess_sol > 0 {
+            transfer_from_sol_vault(&pool, sol_vault_info, sol_vault_bump, recipient_info, system_program_info, excess_sol)?;
+        }
+        
+        if excess_token > 0 {
             invoke_signed(
                 &create_transfer_instruction(
                     &vault_pubkey,
-                    treasury_info.key,
+                    recipient_token_info.key,
                     &vault_pubkey,
-                    amount_b,
+                    excess_token,
                 ),
                 &[
-                    next_account_info(account_info_iter)?.clone(), // vault_info
-                    treasury_info.clone(),
-                    next_account_info(account_info_iter)?.clone(), // vault_info (authority)
+                    vault_info.clone(),
+                    recipient_token_info.clone(),
+                    vault_info.clone(),
                     token_program_info.clone(),
                 ],
-                &[vault_signer_seeds],
+                &[&[b"native_sol_vault", pool.as_ref(), pool_data.token_mint.as_ref(), &[vault_bump]]],
             )?;
         }
         
-        // Update pool state
-        pool_data.fee_collected_sol = pool_data.fee_collected_sol.checked_sub(amount_a).unwrap();
-        pool_data.fee_collected_token = pool_data.fee_collected_token.checked_sub(amount_b).unwrap();
-        
-        // Pack updated pool state
-        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
-        pool_data.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
-        
-        solana_program::log::sol_log("Fees withdrawn successfully");
-        
+        pool_data.check_balances(sol_vault_info, vault_info)?;
+        solana_program::log::sol_log(&format!("Skimmed: {} SOL, {} token", excess_sol, excess_token));
     } else {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(SwapError::PoolTypeMismatch.into());
     }
     
     Ok(())
 }
 
-fn process_set_fee_treasury(
-    _program_id: &Pubkey,
+// Fee of every pool created before fee tiers existed (the old hard-coded 997/1000)
+const LEGACY_FEE_BPS: u16 = 30;
+
+// Admin-only: rewrite a pool account in the current layout, growing it as needed.
+// Legacy pools get the settings they would have had if created today: the config
+// admin as authority, the legacy 30 bps fee and the config's protocol fee share.
+fn process_migrate_pool(
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
     pool: Pubkey,
-    treasury: Pubkey,
 ) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let pool_info = next_account_info(account_info_iter)?;
-    let treasury_info = next_account_info(account_info_iter)?;
-    let _authority_info = next_account_info(account_info_iter)?;
+    let MigratePoolAccounts {
+        pool_info,
+        config_info,
+        admin_info,
+        system_program_info,
+        sol_vault_info,
+    } = MigratePoolAccounts::load(program_id, accounts)?;
     
     // Verify pool account
     if pool_info.key != &pool {
-        return Err(ProgramError::InvalidArgument);
+        return Err(SwapError::InvalidPoolAddress.into());
     }
     
-    // Verify treasury account
-    if treasury_info.key != &treasury {
-        return Err(ProgramError::InvalidArgument);
-    }
+    let config = Config::unpack(&config_info.data.borrow())?;
+    config.check_admin(admin_info)?;
     
-    // Try to deserialize as regular pool first
-    let pool_data_result = Pool::unpack(&pool_info.data.borrow());
-    if let Ok(pool_data) = pool_data_result {
-        // Regular pool treasury setting
-        solana_program::log::sol_log("Setting fee treasury for regular pool");
-        solana_program::log::sol_log(&format!("New treasury: {}", treasury));
-        
-        let mut updated_pool_data = pool_data;
-        updated_pool_data.fee_treasury = treasury;
-        
-        // Pack updated pool state
-        let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
-        updated_pool_data.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
-        
-        solana_program::log::sol_log("Fee treasury set successfully");
-        
-        } else {
-        let pool_data_result = NativeSOLPool::unpack(&pool_info.data.borrow());
-        if let Ok(pool_data) = pool_data_result {
-            // Native SOL pool treasury setting
-            solana_program::log::sol_log("Setting fee treasury for native SOL pool");
-            solana_program::log::sol_log(&format!("New treasury: {}", treasury));
+    match pool_info.data_len() {
+        LegacyPool::LEN => {
+            let legacy = LegacyPool::try_from_slice(&pool_info.data.borrow())
+                .map_err(|_| ProgramError::InvalidAccountData)?;
             
-            let mut updated_pool_data = pool_data;
-            updated_pool_data.fee_treasury = treasury;
+            let migrated = legacy.migrate(&config, Clock::get()?.unix_timestamp);
             
-            // Pack updated pool state
+            resize_program_account(pool_info, admin_info, system_program_info, Pool::LEN)?;
             let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
-            updated_pool_data.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
+            migrated.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
+        }
+        LegacyNativeSOLPool::LEN => {
+            let legacy = LegacyNativeSOLPool::try_from_slice(&pool_info.data.borrow())
+                .map_err(|_| ProgramError::InvalidAccountData)?;
             
-            solana_program::log::sol_log("Fee treasury set successfully");
-        } else {
-            return Err(ProgramError::InvalidAccountData);
+            let sol_vault_info = sol_vault_info.ok_or(ProgramError::NotEnoughAccountKeys)?;
+            if *sol_vault_info.key != get_sol_vault_address(&pool, program_id).0 {
+                return Err(SwapError::InvalidVault.into());
+            }
+            
+            // Legacy native pools kept the SOL reserve and SOL fees in their own lamports
+            // above rent. The admin funds the SOL vault's rent-exempt minimum, then that SOL
+            // moves into the vault.
+            let rent = Rent::get()?;
+            let legacy_sol = math::sub(pool_info.lamports(), rent.minimum_balance(LegacyNativeSOLPool::LEN))?;
+            let sol_vault_rent = rent.minimum_balance(0);
+            if sol_vault_info.lamports() < sol_vault_rent {
+                invoke(
+                    &system_instruction::transfer(
+                        admin_info.key,
+                        sol_vault_info.key,
+                        sol_vault_rent - sol_vault_info.lamports(),
+                    ),
+                    &[
+                        admin_info.clone(),
+                        sol_vault_info.clone(),
+                        system_program_info.clone(),
+                    ],
+                )?;
+            }
+            let pool_lamports = math::sub(pool_info.lamports(), legacy_sol)?;
+            let sol_vault_lamports = math::add(sol_vault_info.lamports(), legacy_sol)?;
+            **pool_info.try_borrow_mut_lamports()? = pool_lamports;
+            **sol_vault_info.try_borrow_mut_lamports()? = sol_vault_lamports;
+            
+            let migrated = legacy.migrate(&config, Clock::get()?.unix_timestamp);
+            
+            resize_program_account(pool_info, admin_info, system_program_info, NativeSOLPool::LEN)?;
+            let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+            migrated.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
         }
+        _ => {
+            // Versioned pools keep their size. Version 1 only lacks the cumulative
+            // prices, which sit in its zeroed reserved bytes and start accruing now.
+            // Pools already in the current layout are left untouched.
+            let account_type = AccountType::of(pool_info)?;
+            if account_type == AccountType::Pool {
+                let mut pool_data = Pool::try_from_slice(
+                    pool_info.data.borrow().get(..Pool::LEN).ok_or(ProgramError::InvalidAccountData)?,
+                )
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+                if pool_data.version == POOL_VERSION {
+                    solana_program::log::sol_log(&format!("Pool already at version {}", POOL_VERSION));
+                    return Ok(());
+                }
+                if pool_data.version != 1 {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                pool_data.version = POOL_VERSION;
+                pool_data.last_update_timestamp = Clock::get()?.unix_timestamp;
+                
+                let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+                pool_data.pack_into_slice(&mut pool_data_mut[..Pool::LEN]);
+            } else if account_type == AccountType::NativeSOLPool {
+                let mut pool_data = NativeSOLPool::try_from_slice(
+                    pool_info.data.borrow().get(..NativeSOLPool::LEN).ok_or(ProgramError::InvalidAccountData)?,
+                )
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+                if pool_data.version == POOL_VERSION {
+                    solana_program::log::sol_log(&format!("Pool already at version {}", POOL_VERSION));
+                    return Ok(());
+                }
+                if pool_data.version != 1 {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                pool_data.version = POOL_VERSION;
+                pool_data.last_update_timestamp = Clock::get()?.unix_timestamp;
+                
+                let mut pool_data_mut = pool_info.try_borrow_mut_data()?;
+                pool_data.pack_into_slice(&mut pool_data_mut[..NativeSOLPool::LEN]);
+            } else {
+                return Err(SwapError::PoolTypeMismatch.into());
+            }
+        }
+    }
+    
+    solana_program::log::sol_log(&format!("Pool {} migrated to version {}", pool, POOL_VERSION));
+    
+    Ok(())
+}
+
+// Grow an account owned by this program to new_len, the payer topping up its rent
+fn resize_program_account<'info>(
+    info: &AccountInfo<'info>,
+    payer_info: &AccountInfo<'info>,
+    system_program_info: &AccountInfo<'info>,
+    new_len: usize,
+) -> ProgramResult {
+    let required_lamports = Rent::get()?.minimum_balance(new_len);
+    if info.lamports() < required_lamports {
+        invoke(
+            &system_instruction::transfer(
+                payer_info.key,
+                info.key,
+                required_lamports - info.lamports(),
+            ),
+            &[
+                payer_info.clone(),
+                info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
+    info.realloc(new_len, true)
+}
+
+// Permissionless: create a pool's observation buffer, paid for by the caller, and
+// record its first observation
+fn process_init_observations(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pool: Pubkey,
+) -> ProgramResult {
+    let InitObservationsAccounts {
+        pool_info,
+        observation_info,
+        payer_info,
+        system_program_info,
+    } = InitObservationsAccounts::load(program_id, accounts)?;
+    
+    // Verify pool account
+    if pool_info.key != &pool {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
+    let observation = observe_pool(pool_info)?;
+    
+    let (observation_pubkey, observation_bump) = get_observation_address(&pool, program_id);
+    if *observation_info.key != observation_pubkey {
+        return Err(SwapError::InvalidObservationAccount.into());
+    }
+    
+    let rent = Rent::get()?;
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_info.key,
+            observation_info.key,
+            rent.minimum_balance(ObservationBuffer::LEN),
+            ObservationBuffer::LEN as u64,
+            program_id,
+        ),
+        &[
+            payer_info.clone(),
+            observation_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&[b"observation", pool.as_ref(), &[observation_bump]]],
+    )?;
+    
+    // The new account is zeroed, so every slot but the first reads as never written
+    let header = ObservationBufferHeader {
+        account_type: AccountType::ObservationBuffer,
+        pool,
+        bump: observation_bump,
+        index: 0,
+    };
+    let mut observation_data = observation_info.try_borrow_mut_data()?;
+    header.pack(&mut observation_data);
+    ObservationBuffer::write_observation(&mut observation_data, 0, &observation);
+    
+    solana_program::log::sol_log(&format!("Observation buffer initialized for pool {}", pool));
+    
+    Ok(())
+}
+
+// Permissionless: append the pool's current cumulative prices to its observation
+// buffer. Keepers and consumers call it to get observations at the times they need.
+fn process_record_observation(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    pool: Pubkey,
+) -> ProgramResult {
+    let RecordObservationAccounts {
+        pool_info,
+        observation_info,
+    } = RecordObservationAccounts::load(program_id, accounts)?;
+    
+    // Verify pool account
+    if pool_info.key != &pool {
+        return Err(SwapError::InvalidPoolAddress.into());
+    }
+    
+    let mut observation_data = observation_info.try_borrow_mut_data()?;
+    let mut header = ObservationBufferHeader::unpack(&observation_data)?;
+    if header.pool != pool {
+        return Err(SwapError::InvalidObservationAccount.into());
+    }
+    
+    let observation = observe_pool(pool_info)?;
+    if !ObservationBuffer::record(&mut header, &mut observation_data, observation)? {
+        solana_program::log::sol_log(&format!("Observation at {} within {}s of the latest", observation.timestamp, MIN_OBSERVATION_INTERVAL));
+        return Ok(());
     }
     
+    solana_program::log::sol_log(&format!("Observation {} recorded at {}", header.index, observation.timestamp));
+    
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn swap_error(error: SwapError) -> ProgramError {
+        error.into()
+    }
+
+    #[test]
+    fn swap_input_is_the_minimal_input_for_the_output() {
+        let cases = [
+            (1_000_000_000, 2_000_000_000, 30, 1),
+            (1_000_000_000, 2_000_000_000, 30, 12_345_678),
+            (1_000_000_000, 2_000_000_000, 100, 1_999_999_999),
+            (5_000, 7_000_000, 1, 6_999),
+            (u64::MAX / 2, u64::MAX / 3, 30, 1_000_000_000_000),
+        ];
+        for (reserve_in, reserve_out, fee_bps, amount_out) in cases {
+            let amount_in = calculate_swap_input(amount_out, reserve_in, reserve_out, fee_bps).unwrap();
+            assert!(calculate_swap_output(amount_in, reserve_in, reserve_out, fee_bps).unwrap() >= amount_out);
+            if amount_in > 1 {
+                assert!(calculate_swap_output(amount_in - 1, reserve_in, reserve_out, fee_bps).unwrap() < amount_out);
+            }
+        }
+    }
+
+    #[test]
+    fn swap_input_rejects_draining_the_output_reserve() {
+        assert_eq!(
+            calculate_swap_input(2_000, 1_000, 2_000, 30),
+            Err(swap_error(SwapError::InsufficientLiquidity))
+        );
+        assert_eq!(
+            calculate_swap_input(2_001, 1_000, 2_000, 30),
+            Err(swap_error(SwapError::InsufficientLiquidity))
+        );
+        assert_eq!(calculate_swap_input(0, 1_000, 2_000, 30), Err(swap_error(SwapError::ZeroAmount)));
+        assert_eq!(calculate_swap_input(1, 0, 2_000, 30), Err(swap_error(SwapError::ZeroLiquidity)));
+    }
+
+    #[test]
+    fn price_impact_is_the_shortfall_from_the_spot_price() {
+        // Spot price 1:1, so 10_000 in is worth 10_000 out
+        assert_eq!(calculate_price_impact_bps(10_000, 10_000, 1_000_000, 1_000_000), Ok(0));
+        assert_eq!(calculate_price_impact_bps(10_000, 9_871, 1_000_000, 1_000_000), Ok(129));
+        assert_eq!(calculate_price_impact_bps(10_000, 4_000, 1_000_000, 500_000), Ok(2_000));
+        // Dust inputs worth nothing at the spot price have no measurable impact
+        assert_eq!(calculate_price_impact_bps(1, 0, 1_000_000, 1), Ok(0));
+        assert_eq!(calculate_price_impact_bps(1, 0, 0, 1_000), Err(swap_error(SwapError::ZeroLiquidity)));
+    }
+
+    #[test]
+    fn swap_limits_reject_slippage_and_price_impact() {
+        assert_eq!(check_swap_limits(10_000, 9_871, 1_000_000, 1_000_000, 9_871, None), Ok(()));
+        assert_eq!(
+            check_swap_limits(10_000, 9_871, 1_000_000, 1_000_000, 9_872, None),
+            Err(swap_error(SwapError::SlippageExceeded))
+        );
+
+        assert_eq!(check_swap_limits(10_000, 9_871, 1_000_000, 1_000_000, 0, Some(129)), Ok(()));
+        assert_eq!(
+            check_swap_limits(10_000, 9_871, 1_000_000, 1_000_000, 0, Some(128)),
+            Err(swap_error(SwapError::PriceImpactExceeded))
+        );
+        // The minimum output is checked first
+        assert_eq!(
+            check_swap_limits(10_000, 9_871, 1_000_000, 1_000_000, 10_000, Some(0)),
+            Err(swap_error(SwapError::SlippageExceeded))
+        );
+    }
+
+    #[test]
+    fn deadline_passes_after_its_timestamp_or_slot() {
+        let clock = Clock {
+            slot: 500,
+            unix_timestamp: 1_700_000_000,
+            ..Clock::default()
+        };
+
+        assert!(!Deadline::UnixTimestamp(1_700_000_001).has_passed(&clock));
+        assert!(!Deadline::UnixTimestamp(1_700_000_000).has_passed(&clock));
+        assert!(Deadline::UnixTimestamp(1_699_999_999).has_passed(&clock));
+
+        assert!(!Deadline::Slot(501).has_passed(&clock));
+        assert!(!Deadline::Slot(500).has_passed(&clock));
+        assert!(Deadline::Slot(499).has_passed(&clock));
+    }
+
+    #[test]
+    fn no_deadline_never_expires() {
+        // Returns before reading the Clock sysvar, which is unavailable off-chain
+        assert_eq!(check_deadline(None), Ok(()));
+    }
+
+    #[test]
+    fn deposits_must_mint_at_least_the_minimum_lp() {
+        assert_eq!(check_liquidity_minted(1_000, 0), Ok(()));
+        assert_eq!(check_liquidity_minted(1_000, 1_000), Ok(()));
+        assert_eq!(check_liquidity_minted(999, 1_000), Err(swap_error(SwapError::SlippageExceeded)));
+        // A deposit minting nothing is rejected even without a minimum
+        assert_eq!(check_liquidity_minted(0, 0), Err(swap_error(SwapError::ZeroAmount)));
+    }
+
+    #[test]
+    fn withdrawals_must_pay_at_least_the_minimum_on_both_sides() {
+        assert_eq!(check_withdrawal_amounts(500, 700, 0, 0), Ok(()));
+        assert_eq!(check_withdrawal_amounts(500, 700, 500, 700), Ok(()));
+        assert_eq!(check_withdrawal_amounts(499, 700, 500, 700), Err(swap_error(SwapError::SlippageExceeded)));
+        assert_eq!(check_withdrawal_amounts(500, 699, 500, 700), Err(swap_error(SwapError::SlippageExceeded)));
+    }
+
+    #[test]
+    fn protocol_fee_is_a_share_of_the_swap_fee() {
+        assert_eq!(split_protocol_fee(1_000_000, 30, 0).unwrap(), (1_000_000, 0));
+        // 30 bps of 1_000_000 is 3_000, a sixth of it goes to the protocol
+        assert_eq!(split_protocol_fee(1_000_000, 30, 6).unwrap(), (999_500, 500));
+        assert_eq!(split_protocol_fee(1_000_000, 100, 2).unwrap(), (995_000, 5_000));
+        // Fees round down, dust inputs pay none
+        assert_eq!(split_protocol_fee(100, 30, 6).unwrap(), (100, 0));
+        let (reserve_credit, protocol_fee) = split_protocol_fee(u64::MAX, 100, 2).unwrap();
+        assert_eq!(reserve_credit + protocol_fee, u64::MAX);
+    }
+
+    #[test]
+    fn reserves_follow_the_vault_balance_net_of_fees() {
+        assert_eq!(reserve_from_balance(1_000, 0), Ok(1_000));
+        assert_eq!(reserve_from_balance(1_000, 250), Ok(750));
+        assert_eq!(reserve_from_balance(250, 250), Ok(0));
+        assert_eq!(reserve_from_balance(249, 250), Err(swap_error(SwapError::VaultBalanceMismatch)));
+    }
+
+    #[test]
+    fn skim_takes_only_the_balance_above_reserve_and_fees() {
+        assert_eq!(vault_excess(1_000, 700, 300), Ok(0));
+        // A direct transfer of 42 into the vault
+        assert_eq!(vault_excess(1_042, 700, 300), Ok(42));
+        assert_eq!(vault_excess(999, 700, 300), Err(swap_error(SwapError::VaultBalanceMismatch)));
+        assert_eq!(vault_excess(u64::MAX, u64::MAX, 1), Err(swap_error(SwapError::MathOverflow)));
+    }
+
+    fn test_config() -> Config {
+        Config {
+            account_type: AccountType::Config,
+            admin: Pubkey::new_unique(),
+            default_fee_bps: 100,
+            protocol_fee_divisor: 6,
+            default_treasury: Pubkey::new_unique(),
+            paused: false,
+            bump: 254,
+        }
+    }
+
+    #[test]
+    fn legacy_pool_migrates_with_current_defaults() {
+        let (token_a, token_b, treasury) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut legacy_data = Vec::new();
+        legacy_data.extend_from_slice(token_a.as_ref());
+        legacy_data.extend_from_slice(token_b.as_ref());
+        legacy_data.push(253);
+        for value in [1_000u64, 2_000, 1_414, 7, 9] {
+            legacy_data.extend_from_slice(&value.to_le_bytes());
+        }
+        legacy_data.extend_from_slice(treasury.as_ref());
+        assert_eq!(legacy_data.len(), LegacyPool::LEN);
+
+        let config = test_config();
+        let pool = LegacyPool::try_from_slice(&legacy_data).unwrap().migrate(&config, 1_700_000_000);
+
+        // State carries over unchanged
+        assert_eq!((pool.token_a, pool.token_b, pool.bump), (token_a, token_b, 253));
+        assert_eq!((pool.reserve_a, pool.reserve_b, pool.total_lp_supply), (1_000, 2_000, 1_414));
+        assert_eq!((pool.fee_collected_a, pool.fee_collected_b, pool.fee_treasury), (7, 9, treasury));
+        // Settings a pool created today would get, except the fee, which stays at the old 30 bps
+        assert_eq!(pool.authority, config.admin);
+        assert_eq!(pool.pending_authority, Pubkey::default());
+        assert_eq!(pool.fee_bps, LEGACY_FEE_BPS);
+        assert_eq!(pool.protocol_fee_divisor, config.protocol_fee_divisor);
+        assert_eq!(pool.status, PoolStatus::Active);
+        assert_eq!((pool.price_a_cumulative, pool.price_b_cumulative, pool.last_update_timestamp), (0, 0, 1_700_000_000));
+
+        let mut data = vec![0; Pool::LEN];
+        pool.pack_into_slice(&mut data);
+        let unpacked = Pool::unpack(&data).unwrap();
+        assert_eq!((unpacked.account_type, unpacked.version), (AccountType::Pool, POOL_VERSION));
+    }
+
+    #[test]
+    fn legacy_native_pool_migrates_with_current_defaults() {
+        let (token_mint, treasury) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let legacy = LegacyNativeSOLPool {
+            token_a: NATIVE_SOL_MINT,
+            token_b: token_mint,
+            bump: 252,
+            reserve_a: 5_000,
+            reserve_b: 8_000,
+            total_lp_supply: 6_324,
+            fee_collected_sol: 11,
+            fee_collected_token: 13,
+            fee_treasury: treasury,
+            token_mint,
+        };
+
+        let config = test_config();
+        let pool = legacy.migrate(&config, 1_700_000_000);
+
+        assert_eq!((pool.token_a, pool.token_b, pool.token_mint), (NATIVE_SOL_MINT, token_mint, token_mint));
+        assert_eq!((pool.reserve_a, pool.reserve_b, pool.total_lp_supply), (5_000, 8_000, 6_324));
+        assert_eq!((pool.fee_collected_sol, pool.fee_collected_token, pool.fee_treasury), (11, 13, treasury));
+        assert_eq!((pool.authority, pool.fee_bps, pool.protocol_fee_divisor), (config.admin, LEGACY_FEE_BPS, 6));
+        assert_eq!((pool.status, pool.last_update_timestamp), (PoolStatus::Active, 1_700_000_000));
+
+        let mut data = vec![0; NativeSOLPool::LEN];
+        pool.pack_into_slice(&mut data);
+        assert_eq!(NativeSOLPool::unpack(&data).unwrap().version, POOL_VERSION);
+    }
+
+    #[test]
+    fn pools_of_an_older_version_need_migration() {
+        assert_eq!(check_pool_version(POOL_VERSION), Ok(()));
+        assert_eq!(check_pool_version(1), Err(swap_error(SwapError::PoolNeedsMigration)));
+    }
+
+    fn split_route(weight_bps: u16, token_path: &[Pubkey]) -> SplitRoute {
+        SplitRoute {
+            weight_bps,
+            token_path: token_path.to_vec(),
+        }
+    }
+
+    #[test]
+    fn split_routes_must_share_endpoints_and_cover_the_input() {
+        let (x, y, z, w) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        assert_eq!(check_split_routes(&[split_route(10_000, &[x, z])]), Ok(()));
+        assert_eq!(check_split_routes(&[split_route(6_000, &[x, z]), split_route(4_000, &[x, y, z])]), Ok(()));
+
+        assert_eq!(
+            check_split_routes(&[split_route(5_000, &[x, z]), split_route(4_000, &[x, y, z])]),
+            Err(swap_error(SwapError::InvalidSplitWeights))
+        );
+        assert_eq!(
+            check_split_routes(&[split_route(10_000, &[x, z]), split_route(0, &[x, y, z])]),
+            Err(swap_error(SwapError::InvalidSplitWeights))
+        );
+        assert_eq!(
+            check_split_routes(&[split_route(5_000, &[x, z]), split_route(5_000, &[x, y])]),
+            Err(swap_error(SwapError::InvalidSwapPath))
+        );
+        assert_eq!(
+            check_split_routes(&[split_route(5_000, &[x, z]), split_route(5_000, &[w, y, z])]),
+            Err(swap_error(SwapError::InvalidSwapPath))
+        );
+        assert_eq!(check_split_routes(&[split_route(10_000, &[x])]), Err(swap_error(SwapError::InvalidSwapPath)));
+        assert_eq!(check_split_routes(&[]), Err(swap_error(SwapError::InvalidSwapPath)));
+    }
+
+    #[test]
+    fn split_amounts_skip_legs_that_round_to_zero() {
+        let (x, z) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let routes = [split_route(6_000, &[x, z]), split_route(4_000, &[x, z])];
+        assert_eq!(split_amounts(1_000_001, &routes).unwrap(), vec![600_000, 400_001]);
+
+        // A 1 bps leg of a small input rounds down to 0, the last route absorbs it
+        let routes = [split_route(1, &[x, z]), split_route(4_999, &[x, z]), split_route(5_000, &[x, z])];
+        assert_eq!(split_amounts(100, &routes).unwrap(), vec![0, 49, 51]);
+        assert_eq!(split_amounts(1, &routes).unwrap(), vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn split_routes_are_bounded() {
+        let (x, y, z, w) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        let too_many_routes = vec![split_route(2_000, &[x, z]); MAX_SPLIT_ROUTES + 1];
+        assert_eq!(check_split_routes(&too_many_routes), Err(swap_error(SwapError::InvalidSwapPath)));
+
+        // 3 routes of 3 hops are 9 hops, one more than MAX_SPLIT_HOPS
+        let long_path = [x, y, w, z];
+        let too_many_hops = [
+            split_route(3_000, &long_path),
+            split_route(3_000, &long_path),
+            split_route(4_000, &long_path),
+        ];
+        assert_eq!(check_split_routes(&too_many_hops), Err(swap_error(SwapError::InvalidSwapPath)));
+        assert_eq!(check_split_routes(&too_many_hops[..2]), Err(swap_error(SwapError::InvalidSplitWeights)));
+    }
+
+    #[test]
+    fn prices_accumulate_over_elapsed_time() {
+        const ONE: u128 = 1 << 64;
+
+        assert_eq!(accumulate_prices((0, 0), 100, 1_000, 1_000, 110), (10 * ONE, 10 * ONE));
+        // A is worth 2 B, B half an A
+        assert_eq!(accumulate_prices((5, 7), 100, 1_000, 2_000, 103), (5 + 6 * ONE, 7 + 3 * ONE / 2));
+        // No time elapsed, a clock behind the last update or an empty pool add nothing
+        assert_eq!(accumulate_prices((5, 7), 100, 1_000, 2_000, 100), (5, 7));
+        assert_eq!(accumulate_prices((5, 7), 100, 1_000, 2_000, 90), (5, 7));
+        assert_eq!(accumulate_prices((5, 7), 100, 0, 2_000, 110), (5, 7));
+    }
+
+    #[test]
+    fn price_accumulators_wrap_and_keep_exact_differences() {
+        const ONE: u128 = 1 << 64;
+
+        let before = (u128::MAX - ONE, u128::MAX);
+        let after = accumulate_prices(before, 0, 1_000, 1_000, 3);
+        assert!(after.0 < before.0 && after.1 < before.1);
+        assert_eq!(after.0.wrapping_sub(before.0), 3 * ONE);
+        assert_eq!(after.1.wrapping_sub(before.1), 3 * ONE);
+    }
+
+    fn observation_at(timestamp: i64) -> Observation {
+        Observation {
+            timestamp,
+            price_a_cumulative: timestamp as u128 * 3,
+            price_b_cumulative: timestamp as u128 * 5,
+        }
+    }
+
+    fn new_observation_buffer(first: Observation) -> (ObservationBufferHeader, Vec<u8>) {
+        let mut data = vec![0; ObservationBuffer::LEN];
+        let header = ObservationBufferHeader {
+            account_type: AccountType::ObservationBuffer,
+            pool: Pubkey::new_unique(),
+            bump: 255,
+            index: 0,
+        };
+        header.pack(&mut data);
+        ObservationBuffer::write_observation(&mut data, 0, &first);
+        (header, data)
+    }
+
+    #[test]
+    fn observations_respect_the_minimum_interval() {
+        let (mut header, mut data) = new_observation_buffer(observation_at(1_000));
+
+        assert!(!ObservationBuffer::record(&mut header, &mut data, observation_at(1_000)).unwrap());
+        assert!(!ObservationBuffer::record(&mut header, &mut data, observation_at(1_000 + MIN_OBSERVATION_INTERVAL - 1)).unwrap());
+        assert_eq!(header.index, 0);
+
+        assert!(ObservationBuffer::record(&mut header, &mut data, observation_at(1_000 + MIN_OBSERVATION_INTERVAL)).unwrap());
+        assert_eq!(header.index, 1);
+        assert_eq!(ObservationBufferHeader::unpack(&data).unwrap(), header);
+        assert_eq!(ObservationBuffer::read_observation(&data, 0).unwrap(), observation_at(1_000));
+        assert_eq!(
+            ObservationBuffer::read_observation(&data, 1).unwrap(),
+            observation_at(1_000 + MIN_OBSERVATION_INTERVAL)
+        );
+    }
+
+    #[test]
+    fn observation_buffer_wraps_around_overwriting_the_oldest() {
+        let (mut header, mut data) = new_observation_buffer(observation_at(1_000));
+
+        let timestamp_of = |n: usize| 1_000 + n as i64 * MIN_OBSERVATION_INTERVAL;
+        for n in 1..=OBSERVATION_CAPACITY + 1 {
+            assert!(ObservationBuffer::record(&mut header, &mut data, observation_at(timestamp_of(n))).unwrap());
+        }
+
+        // The first observation and the one after it were overwritten
+        assert_eq!(header.index, 1);
+        assert_eq!(ObservationBufferHeader::unpack(&data).unwrap(), header);
+        assert_eq!(ObservationBuffer::read_observation(&data, 0).unwrap(), observation_at(timestamp_of(OBSERVATION_CAPACITY)));
+        assert_eq!(ObservationBuffer::read_observation(&data, 1).unwrap(), observation_at(timestamp_of(OBSERVATION_CAPACITY + 1)));
+        // The oldest observation left is the slot after the latest
+        assert_eq!(ObservationBuffer::read_observation(&data, 2).unwrap(), observation_at(timestamp_of(2)));
+    }
+
+    #[test]
+    fn observation_header_rejects_other_accounts() {
+        let (mut header, mut data) = new_observation_buffer(observation_at(1_000));
+
+        assert_eq!(ObservationBufferHeader::unpack(&data[..ObservationBuffer::LEN - 1]), Err(ProgramError::InvalidAccountData));
+
+        header.index = OBSERVATION_CAPACITY as u16;
+        header.pack(&mut data);
+        assert_eq!(ObservationBufferHeader::unpack(&data), Err(ProgramError::InvalidAccountData));
+
+        header.index = 0;
+        header.account_type = AccountType::Pool;
+        header.pack(&mut data);
+        assert!(ObservationBufferHeader::unpack(&data).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+// Checked arithmetic shared by the instruction handlers. Every helper fails with
+// SwapError::MathOverflow instead of panicking, and narrowing casts go through
+// u64::try_from so results never truncate silently.
+
+use solana_program::program_error::ProgramError;
+
+use crate::SwapError;
+
+// Narrow a u128 intermediate result back to u64
+pub(crate) fn to_u64(value: u128) -> Result<u64, ProgramError> {
+    u64::try_from(value).map_err(|_| SwapError::MathOverflow.into())
+}
+
+pub(crate) fn add(a: u64, b: u64) -> Result<u64, ProgramError> {
+    a.checked_add(b).ok_or_else(|| SwapError::MathOverflow.into())
+}
+
+pub(crate) fn sub(a: u64, b: u64) -> Result<u64, ProgramError> {
+    a.checked_sub(b).ok_or_else(|| SwapError::MathOverflow.into())
+}
+
+// value * numerator / denominator in u128, rounded down
+pub(crate) fn mul_div(value: u64, numerator: u64, denominator: u64) -> Result<u64, ProgramError> {
+    let product = (value as u128)
+        .checked_mul(numerator as u128)
+        .ok_or(SwapError::MathOverflow)?;
+    let quotient = product
+        .checked_div(denominator as u128)
+        .ok_or(SwapError::MathOverflow)?;
+    to_u64(quotient)
+}
+
+// Exact product of two u64 values, for intermediates that need the full width
+pub(crate) fn wide_mul(a: u64, b: u64) -> u128 {
+    a as u128 * b as u128
+}
+
+// u128 intermediate scaled by a u64 factor
+pub(crate) fn mul_wide(a: u128, b: u64) -> Result<u128, ProgramError> {
+    a.checked_mul(b as u128).ok_or_else(|| SwapError::MathOverflow.into())
+}
+
+pub(crate) fn add_wide(a: u128, b: u128) -> Result<u128, ProgramError> {
+    a.checked_add(b).ok_or_else(|| SwapError::MathOverflow.into())
+}
+
+// Quotient of u128 intermediates, rounded down
+pub(crate) fn div_wide(numerator: u128, denominator: u128) -> Result<u128, ProgramError> {
+    numerator.checked_div(denominator).ok_or_else(|| SwapError::MathOverflow.into())
+}
+
+// Quotient of u128 intermediates, rounded up
+pub(crate) fn div_wide_ceil(numerator: u128, denominator: u128) -> Result<u128, ProgramError> {
+    if denominator == 0 {
+        return Err(SwapError::MathOverflow.into());
+    }
+    Ok(numerator.div_ceil(denominator))
+}
+
+// Geometric mean sqrt(a * b), rounded down
+pub(crate) fn sqrt_product(a: u64, b: u64) -> Result<u64, ProgramError> {
+    let product = (a as u128)
+        .checked_mul(b as u128)
+        .ok_or(SwapError::MathOverflow)?;
+    to_u64(product.integer_sqrt())
+}
+
+// Integer square root implementation for u128
+pub(crate) trait IntegerSqrt {
+    fn integer_sqrt(self) -> Self;
+}
+
+impl IntegerSqrt for u128 {
+    fn integer_sqrt(self) -> Self {
+        if self < 2 {
+            return self;
+        }
+        let mut x = self;
+        let mut y = self.div_ceil(2);
+        while y < x {
+            x = y;
+            y = (x + self / x) / 2;
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overflow() -> ProgramError {
+        SwapError::MathOverflow.into()
+    }
+
+    #[test]
+    fn to_u64_rejects_values_above_u64_max() {
+        assert_eq!(to_u64(u64::MAX as u128), Ok(u64::MAX));
+        assert_eq!(to_u64(u64::MAX as u128 + 1), Err(overflow()));
+        assert_eq!(to_u64(u128::MAX), Err(overflow()));
+    }
+
+    #[test]
+    fn mul_div_rounds_down_and_rejects_zero_and_overflow() {
+        assert_eq!(mul_div(10, 2, 3), Ok(6));
+        // The u128 intermediate does not overflow, only the result can
+        assert_eq!(mul_div(u64::MAX, u64::MAX, u64::MAX), Ok(u64::MAX));
+        assert_eq!(mul_div(u64::MAX, 2, 1), Err(overflow()));
+        assert_eq!(mul_div(1, 1, 0), Err(overflow()));
+    }
+
+    #[test]
+    fn div_wide_ceil_rounds_up_and_rejects_zero() {
+        assert_eq!(div_wide_ceil(6, 3), Ok(2));
+        assert_eq!(div_wide_ceil(7, 3), Ok(3));
+        assert_eq!(div_wide_ceil(0, 3), Ok(0));
+        assert_eq!(div_wide_ceil(u128::MAX, 2), Ok(u128::MAX / 2 + 1));
+        assert_eq!(div_wide_ceil(1, 0), Err(overflow()));
+        assert_eq!(div_wide(1, 0), Err(overflow()));
+    }
+
+    #[test]
+    fn sqrt_product_fits_u64_at_the_extremes() {
+        assert_eq!(sqrt_product(u64::MAX, u64::MAX), Ok(u64::MAX));
+        assert_eq!(sqrt_product(u64::MAX, 1), Ok(u32::MAX as u64));
+        assert_eq!(sqrt_product(0, u64::MAX), Ok(0));
+    }
+
+    #[test]
+    fn integer_sqrt_rounds_down_around_perfect_squares() {
+        assert_eq!(0u128.integer_sqrt(), 0);
+        assert_eq!(1u128.integer_sqrt(), 1);
+        for root in [2u128, 3, 10, 1_000_003, u32::MAX as u128, u64::MAX as u128] {
+            let square = root * root;
+            assert_eq!((square - 1).integer_sqrt(), root - 1);
+            assert_eq!(square.integer_sqrt(), root);
+            assert_eq!((square + 1).integer_sqrt(), root);
+        }
+        assert_eq!(u128::MAX.integer_sqrt(), u64::MAX as u128);
+    }
+}